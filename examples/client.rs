@@ -0,0 +1,18 @@
+//! Stream a sine wave to a running viewer:
+//! cargo run --example client
+use dataviewer::{client::Client, dataview};
+
+fn main() -> eyre::Result<()> {
+    let mut file = dataview::File::default();
+    file.dataview.title = Some("Sine".into());
+    file.chart.insert("1".into(), dataview::Chart::default());
+
+    let mut client = Client::connect_default()?;
+    client.declare(file)?;
+    for i in 0..1000 {
+        let x = i as f64 / 100.0;
+        client.push("1", x, x.sin())?;
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    Ok(())
+}
@@ -48,4 +48,9 @@ main()
     done
 }
 
-main | nc -U /tmp/dataviewer.ipc
+# Stream to the viewer socket, unless the output is piped (e.g. to dataviewer -)
+if [ -t 1 ]; then
+    main | nc -U "${DATAVIEWER_SOCKET:-/tmp/dataviewer.ipc}"
+else
+    main
+fi
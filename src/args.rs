@@ -0,0 +1,90 @@
+use crate::csv;
+use eyre::{eyre, Result};
+use std::path::PathBuf;
+
+/// Command line arguments of the application
+#[derive(Debug, Default, Clone)]
+pub struct Args {
+    /// Run in the background to receive streams (--stream)
+    pub stream: bool,
+    /// IPC sockets to listen on (--socket PATH, may be repeated)
+    pub sockets: Vec<PathBuf>,
+    /// TCP addresses to listen on (--listen ADDRESS:PORT, may be repeated)
+    pub listen: Vec<String>,
+    /// Read a stream of messages from the standard input (--stdin or -)
+    pub stdin: bool,
+    /// Files to follow while they are written (--follow PATH, may be repeated)
+    pub follow: Vec<PathBuf>,
+    /// Commands to run, reading their output (--exec COMMAND, may be repeated)
+    pub exec: Vec<String>,
+    /// Reload the files when they are modified (--auto-reload)
+    pub auto_reload: bool,
+    /// Options to import CSV files
+    /// (--x-column COLUMN, --columns COLUMN,COLUMN..., --delimiter CHAR)
+    pub csv: csv::Options,
+    /// Files to open
+    pub files: Vec<PathBuf>,
+}
+
+/// Get the value of an option from the next argument
+pub fn value<T, I: Iterator<Item = T>>(iter: &mut I, arg: &str) -> Result<T> {
+    iter.next().ok_or_else(|| eyre!("{} requires a value", arg))
+}
+
+impl Args {
+    /// Parse the command line arguments (without the program name)
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut iter = args.into_iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--stream" => parsed.stream = true,
+                "--stdin" | "-" => parsed.stdin = true,
+                "--auto-reload" => parsed.auto_reload = true,
+                "--socket" => {
+                    parsed.sockets.push(PathBuf::from(value(&mut iter, &arg)?));
+                }
+                "--follow" => {
+                    parsed.follow.push(PathBuf::from(value(&mut iter, &arg)?));
+                }
+                "--exec" => {
+                    parsed.exec.push(value(&mut iter, &arg)?);
+                }
+                "--x-column" => {
+                    parsed.csv.x_column = Some(value(&mut iter, &arg)?);
+                }
+                "--columns" => {
+                    let value = value(&mut iter, &arg)?;
+                    let columns = value.split(',').map(|column| column.trim().to_string());
+                    parsed.csv.columns = Some(columns.collect());
+                }
+                "--delimiter" => {
+                    let value = value(&mut iter, &arg)?;
+                    let delimiter = match value.as_str() {
+                        "\\t" | "tab" => '\t',
+                        _ => {
+                            let mut chars = value.chars();
+                            match (chars.next(), chars.next()) {
+                                (Some(c), None) => c,
+                                _ => {
+                                    return Err(eyre!("Invalid delimiter '{}'", value));
+                                }
+                            }
+                        }
+                    };
+                    parsed.csv.delimiter = Some(delimiter);
+                }
+                "--listen" => {
+                    parsed.listen.push(value(&mut iter, &arg)?);
+                }
+                _ => {
+                    if arg.starts_with("--") {
+                        return Err(eyre!("Unknown option '{}'", arg));
+                    }
+                    parsed.files.push(PathBuf::from(arg));
+                }
+            }
+        }
+        Ok(parsed)
+    }
+}
@@ -1,7 +1,9 @@
 use crate::chart::View;
 use crate::dataview;
+use ::dataviewer::ticks::Ticks;
 use gtk::cairo;
 use gtk4 as gtk;
+use std::collections::HashMap;
 
 pub struct Canvas<'a> {
     cairo: &'a cairo::Context,
@@ -10,16 +12,22 @@ pub struct Canvas<'a> {
     mouse_x: f64,
     mouse_y: f64,
     view: View,
+    major_grid: bool,
+    minor_grid: bool,
+    colors: Option<&'a HashMap<String, Color>>,
 }
 
 pub struct Tooltip {
     pub key: String,
+    /// Label displayed instead of the x value (e.g. a category name)
+    pub xlabel: Option<String>,
     pub xpixel: f64,
     pub ypixel: f64,
     pub x: f64,
     pub y: f64,
 }
 
+#[derive(Debug, Clone)]
 pub struct Color {
     pub red: f64,
     pub green: f64,
@@ -37,6 +45,24 @@ pub static BLACK: Color = Color {
     blue: 0.0,
 };
 
+static GREY: Color = Color {
+    red: 0.6,
+    green: 0.6,
+    blue: 0.6,
+};
+
+static MAJOR_GRID: Color = Color {
+    red: 0.85,
+    green: 0.85,
+    blue: 0.85,
+};
+
+static MINOR_GRID: Color = Color {
+    red: 0.94,
+    green: 0.94,
+    blue: 0.94,
+};
+
 impl Color {
     pub fn rgb(value: u32) -> Self {
         Self {
@@ -78,7 +104,6 @@ impl Palette {
 
 impl<'a> Canvas<'a> {
     pub fn new(
-        _area: &'a gtk::DrawingArea,
         cairo: &'a cairo::Context,
         width: i32,
         height: i32,
@@ -93,9 +118,31 @@ impl<'a> Canvas<'a> {
             mouse_x,
             mouse_y,
             view: view.clone(),
+            major_grid: false,
+            minor_grid: false,
+            colors: None,
+        }
+    }
+
+    /// Set the color bound to each series
+    pub fn set_colors(&mut self, colors: &'a HashMap<String, Color>) {
+        self.colors = Some(colors);
+    }
+
+    /// Return the color of a series
+    pub fn color(&self, key: &str) -> Color {
+        match self.colors.and_then(|colors| colors.get(key)) {
+            Some(color) => color.clone(),
+            None => BLACK.clone(),
         }
     }
 
+    /// Draw the grid lines enabled in the [dataview] table
+    pub fn set_grid(&mut self, dataview: &dataview::DataView) {
+        self.major_grid = dataview.major_grid.unwrap_or(false);
+        self.minor_grid = dataview.minor_grid.unwrap_or(false);
+    }
+
     fn x_pixel(&self, x: f64) -> f64 {
         // <-----------width------------>
         //
@@ -117,16 +164,33 @@ impl<'a> Canvas<'a> {
         self.height - y_norm * self.height
     }
 
-    /*
-    pub fn rectangle(&self, x: f64, y: f64) -> &Self {
-        self.cairo.rectangle(self.x_pixel(x), self.y_pixel(y), 5.0, 5.0);
-        self
+    /// Add the rectangle going from (x0, y0) to (x1, y1) to the current path
+    /// and return it in pixels as (left, top, right, bottom)
+    pub fn rectangle(&self, x0: f64, y0: f64, x1: f64, y1: f64) -> (f64, f64, f64, f64) {
+        let (left, right) = (self.x_pixel(x0), self.x_pixel(x1));
+        let (bottom, top) = (self.y_pixel(y0), self.y_pixel(y1));
+        let (left, right) = (left.min(right), left.max(right));
+        let (top, bottom) = (top.min(bottom), top.max(bottom));
+        self.cairo.rectangle(left, top, right - left, bottom - top);
+        (left, top, right, bottom)
     }
-    */
 
-    pub fn move_to(&self, x: f64, y: f64) -> &Self {
-        self.cairo.move_to(self.x_pixel(x), self.y_pixel(y));
-        self
+    /// Return the x value of a pixel column
+    pub fn x_value(&self, x_pixel: f64) -> f64 {
+        let x_range = self.view.x_max - self.view.x_min;
+        self.view.x_min + (x_pixel / self.width) * x_range
+    }
+
+    /// Return the pixel of the point (x, y)
+    pub fn pixel(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.x_pixel(x), self.y_pixel(y))
+    }
+
+    pub fn move_to(&self, x: f64, y: f64) -> (f64, f64) {
+        let x = self.x_pixel(x);
+        let y = self.y_pixel(y);
+        self.cairo.move_to(x, y);
+        (x, y)
     }
 
     pub fn line_to(&self, x: f64, y: f64) -> (f64, f64) {
@@ -143,33 +207,29 @@ impl<'a> Canvas<'a> {
         (x, y)
     }
 
-    /// Try to format float to a nice viewable string for user.
-    /// We try to take in account the user view:
-    /// - if user zoom in, we add more decimal
-    /// - if user zoom out, we remove unecessart decimal
-    fn fmtfloat(val: f64, view_range: f64) -> String {
-        let abs = view_range;
-        if abs > 10000.0 {
-            format!("{:.0}", val)
-        } else if abs > 1000.0 {
-            format!("{:.1}", val)
-        } else if abs > 100.0 {
-            format!("{:.2}", val)
-        } else if abs > 10.0 {
-            format!("{:.3}", val)
-        } else if abs > 0.01 {
-            format!("{:.4}", val)
-        } else if abs > 0.001 {
-            format!("{:.5}", val)
-        } else if abs > 0.0001 {
-            format!("{:.6}", val)
-        } else if abs > 0.00001 {
-            format!("{:.7}", val)
-        } else {
-            format!("{:.8}", val)
+    fn text_width(&self, text: &str) -> f64 {
+        match self.cairo.text_extents(text) {
+            Ok(extents) => extents.width(),
+            Err(_) => 0.0,
         }
     }
 
+    fn x_ticks(&self) -> Option<Ticks> {
+        Ticks::fit(
+            self.view.x_min,
+            self.view.x_max,
+            self.width,
+            80.0,
+            |label| self.text_width(label),
+        )
+    }
+
+    fn y_ticks(&self) -> Option<Ticks> {
+        Ticks::fit(self.view.y_min, self.view.y_max, self.height, 30.0, |_| {
+            12.0
+        })
+    }
+
     fn x_axis_pos(&self) -> f64 {
         let margin = 30.0;
         let mut y0 = self.y_pixel(0.0);
@@ -192,38 +252,144 @@ impl<'a> Canvas<'a> {
         x0
     }
 
-    pub fn draw_axis(&self) -> &Self {
-        self.set_color(&BLACK);
-        let x0 = self.y_axis_pos();
+    /// Draw the vertical grid lines at the x ticks
+    fn draw_x_grid(&self) -> &Self {
+        let ticks = match self.x_ticks() {
+            Some(ticks) => ticks,
+            None => {
+                return self;
+            }
+        };
+        let (x_min, x_max) = (self.view.x_min, self.view.x_max);
+        if self.minor_grid {
+            self.set_color(&MINOR_GRID);
+            for x in ticks.minor(x_min, x_max) {
+                self.cairo.move_to(self.x_pixel(x), 0.0);
+                self.cairo.line_to(self.x_pixel(x), self.height);
+            }
+            self.stroke();
+        }
+        if self.major_grid {
+            self.set_color(&MAJOR_GRID);
+            for x in ticks.major(x_min, x_max) {
+                self.cairo.move_to(self.x_pixel(x), 0.0);
+                self.cairo.line_to(self.x_pixel(x), self.height);
+            }
+            self.stroke();
+        }
+        self
+    }
+
+    /// Draw the horizontal grid lines at the y ticks
+    fn draw_y_grid(&self) -> &Self {
+        let ticks = match self.y_ticks() {
+            Some(ticks) => ticks,
+            None => {
+                return self;
+            }
+        };
+        let (y_min, y_max) = (self.view.y_min, self.view.y_max);
+        if self.minor_grid {
+            self.set_color(&MINOR_GRID);
+            for y in ticks.minor(y_min, y_max) {
+                self.cairo.move_to(0.0, self.y_pixel(y));
+                self.cairo.line_to(self.width, self.y_pixel(y));
+            }
+            self.stroke();
+        }
+        if self.major_grid {
+            self.set_color(&MAJOR_GRID);
+            for y in ticks.major(y_min, y_max) {
+                self.cairo.move_to(0.0, self.y_pixel(y));
+                self.cairo.line_to(self.width, self.y_pixel(y));
+            }
+            self.stroke();
+        }
+        self
+    }
+
+    fn draw_x_axis(&self) -> &Self {
         let y0 = self.x_axis_pos();
-        let x_range = self.view.x_max - self.view.x_min;
-        let y_range = self.view.y_max - self.view.y_min;
 
-        // Draw X Axis
         self.cairo.move_to(0.0, y0);
         self.cairo.line_to(self.width, y0);
-        for i in 0..11 {
-            let i = i as f64;
-            let step = x_range / 10.0;
-            let start = (self.view.x_min / step).floor() * step;
-            let x = start + (i * step);
-            self.cairo.move_to(self.x_pixel(x), y0 + 10.0);
-            let _ = self.cairo.show_text(&Self::fmtfloat(x, x_range));
+        let ticks = match self.x_ticks() {
+            Some(ticks) => ticks,
+            None => {
+                return self;
+            }
+        };
+        for x in ticks.major(self.view.x_min, self.view.x_max) {
+            let label = ticks.format(x);
+            let xpixel = self.x_pixel(x);
+            self.cairo.move_to(xpixel, y0);
+            self.cairo.line_to(xpixel, y0 + 4.0);
+            self.cairo
+                .move_to(xpixel - self.text_width(&label) / 2.0, y0 + 15.0);
+            let _ = self.cairo.show_text(&label);
         }
+        self
+    }
+
+    fn draw_y_axis(&self) -> &Self {
+        let x0 = self.y_axis_pos();
 
-        // Draw Y Axis
         self.cairo.move_to(x0, 0.0);
         self.cairo.line_to(x0, self.height);
-        for i in 0..10 {
-            let i = i as f64;
-            let step = y_range / 10.0;
-            let start = (self.view.y_min / step).floor() * step;
-            let y = start + (i * step);
-            self.cairo.move_to(x0 - 40.0, self.y_pixel(y));
-            let _ = self.cairo.show_text(&Self::fmtfloat(y, y_range));
+        let ticks = match self.y_ticks() {
+            Some(ticks) => ticks,
+            None => {
+                return self;
+            }
+        };
+        for y in ticks.major(self.view.y_min, self.view.y_max) {
+            let label = ticks.format(y);
+            let ypixel = self.y_pixel(y);
+            self.cairo.move_to(x0, ypixel);
+            self.cairo.line_to(x0 - 4.0, ypixel);
+            self.cairo
+                .move_to(x0 - 6.0 - self.text_width(&label), ypixel + 4.0);
+            let _ = self.cairo.show_text(&label);
         }
+        self
+    }
 
-        self.stroke()
+    pub fn draw_axis(&self) -> &Self {
+        self.draw_x_grid().draw_y_grid();
+        self.set_color(&BLACK);
+        self.draw_x_axis().draw_y_axis().stroke()
+    }
+
+    /// Draw the axis with a category label under each integer x value
+    /// (x = 0 for the first category, x = 1 for the second, ...)
+    pub fn draw_category_axis(&self, categories: &[String]) -> &Self {
+        self.draw_y_grid();
+        self.set_color(&BLACK);
+        let y0 = self.x_axis_pos();
+        self.cairo.move_to(0.0, y0);
+        self.cairo.line_to(self.width, y0);
+
+        // Skip some labels if they do not fit in their slot
+        let slot = self.x_pixel(1.0) - self.x_pixel(0.0);
+        let mut label_width: f64 = 1.0;
+        for category in categories {
+            if let Ok(extents) = self.cairo.text_extents(category) {
+                label_width = label_width.max(extents.width() + 10.0);
+            }
+        }
+        let every = (label_width / slot).ceil().max(1.0) as usize;
+
+        for (i, category) in categories.iter().enumerate().step_by(every) {
+            let width = match self.cairo.text_extents(category) {
+                Ok(extents) => extents.width(),
+                Err(_) => 0.0,
+            };
+            self.cairo
+                .move_to(self.x_pixel(i as f64) - width / 2.0, y0 + 12.0);
+            let _ = self.cairo.show_text(category);
+        }
+
+        self.draw_y_axis().stroke()
     }
 
     /// Draw main title centered at the top of the canvas
@@ -288,6 +454,79 @@ impl<'a> Canvas<'a> {
         self.stroke()
     }
 
+    /// Draw the legend of the series, in the same colors than the chart,
+    /// and return the area of each entry in pixels as (left, top, right, bottom)
+    pub fn draw_legend(&self, file: &dataview::File) -> Vec<(String, (f64, f64, f64, f64))> {
+        let position = file.dataview.legend.unwrap_or_default();
+        if position == dataview::Legend::None || file.data.is_empty() {
+            return vec![];
+        }
+
+        let fontsize = 12.0;
+        let line = 18.0;
+        let padding = 8.0;
+        let swatch = 10.0;
+        self.cairo.set_font_size(fontsize);
+
+        let entries: Vec<(&String, String, Color)> = file
+            .data
+            .keys()
+            .map(|key| {
+                let title = file.chart.get(key).and_then(|chart| chart.title.clone());
+                (key, title.unwrap_or_else(|| key.clone()), self.color(key))
+            })
+            .collect();
+        let text_width = entries
+            .iter()
+            .map(|(_, title, _)| self.text_width(title))
+            .fold(0.0, f64::max);
+        let width = padding + swatch + 6.0 + text_width + padding;
+        let height = 2.0 * padding + line * (entries.len() as f64) - (line - swatch);
+
+        let (left, top) = match position {
+            dataview::Legend::TopLeft => (self.y_axis_pos() + 10.0, 55.0),
+            dataview::Legend::BottomRight => {
+                (self.width - width - 10.0, self.height - height - 40.0)
+            }
+            dataview::Legend::BottomLeft => (self.y_axis_pos() + 10.0, self.height - height - 40.0),
+            _ => (self.width - width - 10.0, 35.0),
+        };
+
+        self.cairo.set_source_rgba(1.0, 1.0, 1.0, 0.8);
+        self.cairo.rectangle(left, top, width, height);
+        self.fill_preserve();
+        self.set_color(&GREY);
+        self.stroke();
+
+        let mut areas = vec![];
+        for (i, (key, title, color)) in entries.iter().enumerate() {
+            let y = top + padding + line * (i as f64);
+            let hidden = file.is_hidden(key);
+            self.cairo.rectangle(left + padding, y, swatch, swatch);
+            match hidden {
+                true => self.set_color(&GREY).stroke(),
+                false => self.set_color(color).fill(),
+            };
+            self.set_color(match hidden {
+                true => &GREY,
+                false => &BLACK,
+            });
+            self.cairo
+                .move_to(left + padding + swatch + 6.0, y + swatch);
+            let _ = self.cairo.show_text(title);
+            self.stroke();
+
+            let entry = (
+                left,
+                y - (line - swatch) / 2.0,
+                left + width,
+                y + swatch + (line - swatch) / 2.0,
+            );
+            areas.push((key.to_string(), entry));
+        }
+        areas
+    }
+
     pub fn draw_multiline_text(
         &self,
         text: &str,
@@ -345,7 +584,10 @@ impl<'a> Canvas<'a> {
             Some(x_unit) => x_unit,
             None => "",
         };
-        text += &format!("{}: {} {}\n", x_title, tooltip.x, x_unit);
+        match &tooltip.xlabel {
+            Some(xlabel) => text += &format!("{}: {}\n", x_title, xlabel),
+            None => text += &format!("{}: {} {}\n", x_title, tooltip.x, x_unit),
+        }
 
         let y_title = match &file.dataview.y_title {
             Some(y_title) => y_title,
@@ -366,11 +608,27 @@ impl<'a> Canvas<'a> {
         self
     }
 
+    pub fn set_color_alpha(&self, color: &Color, alpha: f64) -> &Self {
+        self.cairo
+            .set_source_rgba(color.red, color.green, color.blue, alpha);
+        self
+    }
+
     pub fn stroke(&self) -> &Self {
         self.cairo.stroke().expect("Cairo stroke failed");
         self
     }
 
+    pub fn fill(&self) -> &Self {
+        self.cairo.fill().expect("Cairo fill failed");
+        self
+    }
+
+    pub fn fill_preserve(&self) -> &Self {
+        self.cairo.fill_preserve().expect("Cairo fill failed");
+        self
+    }
+
     pub fn draw(&self, file: &dataview::File) -> &Self {
         self.draw_main_title(file)
             .draw_x_title(file)
@@ -378,6 +636,14 @@ impl<'a> Canvas<'a> {
         self
     }
 
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+
+    pub fn view(&self) -> &View {
+        &self.view
+    }
+
     pub fn mouse_x(&self) -> f64 {
         self.mouse_x
     }
@@ -0,0 +1,79 @@
+use crate::canvas::{Canvas, Tooltip};
+use crate::chart::{Chart, View};
+use crate::dataview;
+
+// Plot a Bar Chart:
+// Each series is a list of heights, the value at index i
+// being the height of the bar for the category i.
+// Series sharing the same categories are drawn as grouped bars.
+#[derive(Default)]
+pub struct Bar;
+
+impl Bar {
+    /// Return the name of each category
+    fn categories(file: &dataview::File) -> Vec<String> {
+        let mut categories = file.dataview.categories.clone().unwrap_or_default();
+
+        // Categories without a name are named after their index
+        let count = file.data.values().map(|data| data.len()).max();
+        for i in categories.len()..count.unwrap_or(0) {
+            categories.push(i.to_string());
+        }
+        categories
+    }
+}
+
+impl Chart for Bar {
+    fn view(&self, file: &dataview::File) -> View {
+        let count = Self::categories(file).len().max(1);
+        let mut view = View::line_minmax(file, 0.0, 1.0);
+        if view.y_min > view.y_max {
+            view.y_min = 0.0;
+            view.y_max = 1.0;
+        }
+        view.x_min = -0.5;
+        view.x_max = (count as f64) - 0.5;
+        view.show_axis().margin()
+    }
+
+    fn draw(&self, canvas: &Canvas, file: &dataview::File) {
+        let mut tooltip = None;
+        let categories = Self::categories(file);
+        canvas.draw_category_axis(&categories);
+
+        // Bars of a same category share 80% of the category slot
+        let series = file.data.len().max(1) as f64;
+        let width = 0.8 / series;
+
+        for (n, (key, data)) in file.data.iter().enumerate() {
+            let color = canvas.color(key);
+            canvas.set_color(&color);
+            if file.is_hidden(key) {
+                continue;
+            }
+
+            for (i, y) in data.iter().enumerate() {
+                let x0 = (i as f64) - 0.4 + (n as f64) * width;
+                let x1 = x0 + width;
+                let (left, top, right, bottom) = canvas.rectangle(x0, 0.0, x1, *y);
+
+                let (mouse_x, mouse_y) = (canvas.mouse_x(), canvas.mouse_y());
+                if mouse_x >= left && mouse_x <= right && mouse_y >= top && mouse_y <= bottom {
+                    tooltip = Some(Tooltip {
+                        key: key.clone(),
+                        xlabel: Some(categories[i].clone()),
+                        x: i as f64,
+                        y: *y,
+                        xpixel: (left + right) / 2.0,
+                        ypixel: top,
+                    });
+                }
+            }
+            canvas.fill();
+        }
+
+        if let Some(tooltip) = &tooltip {
+            canvas.draw_tooltip(file, tooltip);
+        }
+    }
+}
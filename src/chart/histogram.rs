@@ -0,0 +1,82 @@
+use crate::canvas::{Canvas, Tooltip};
+use crate::chart::{Chart, View};
+use crate::dataview;
+use ::dataviewer::histogram;
+
+// Plot an Histogram:
+// Each series is a flat list of raw samples which are counted in bins.
+#[derive(Default)]
+pub struct Histogram {
+    histogram: histogram::Histogram,
+}
+
+impl Chart for Histogram {
+    fn update(&mut self, file: &dataview::File) {
+        self.histogram.update(file);
+    }
+
+    fn view(&self, file: &dataview::File) -> View {
+        let density = file.dataview.density.unwrap_or(false);
+        let mut view = View::new();
+        for key in file.data.keys() {
+            if file.is_hidden(key) {
+                continue;
+            }
+            for bin in self.histogram.bins(key, density) {
+                view.x_min = view.x_min.min(bin.left);
+                view.x_max = view.x_max.max(bin.right);
+                view.y_max = view.y_max.max(bin.height);
+            }
+        }
+
+        if view.x_min > view.x_max {
+            return View {
+                x_min: -1.0,
+                x_max: 1.0,
+                y_min: -1.0,
+                y_max: 1.0,
+            };
+        }
+        view.y_min = 0.0;
+        view.show_axis().margin()
+    }
+
+    fn draw(&self, canvas: &Canvas, file: &dataview::File) {
+        let density = file.dataview.density.unwrap_or(false);
+        let mut tooltip = None;
+        canvas.draw_axis();
+
+        for key in file.data.keys() {
+            let color = canvas.color(key);
+            if file.is_hidden(key) {
+                continue;
+            }
+            for bin in self.histogram.bins(key, density) {
+                let (left, right, height) = (bin.left, bin.right, bin.height);
+                let (xleft, ytop, xright, ybottom) = canvas.rectangle(left, 0.0, right, height);
+
+                let (mouse_x, mouse_y) = (canvas.mouse_x(), canvas.mouse_y());
+                if mouse_x >= xleft && mouse_x <= xright && mouse_y >= ytop && mouse_y <= ybottom {
+                    tooltip = Some(Tooltip {
+                        key: key.clone(),
+                        xlabel: Some(format!("[{}, {})", left, right)),
+                        x: bin.center(),
+                        y: height,
+                        xpixel: (xleft + xright) / 2.0,
+                        ypixel: ytop,
+                    });
+                }
+            }
+
+            // Bins of the different series may overlap
+            canvas.set_color_alpha(&color, 0.5);
+            canvas.fill_preserve();
+            canvas.set_color(&color);
+            canvas.stroke();
+        }
+
+        if let Some(tooltip) = &tooltip {
+            canvas.draw_tooltip(file, tooltip);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use crate::canvas::Canvas;
+use crate::chart::lod::{self, Lod};
+use crate::chart::{draw_series, Chart, Points, View};
+use crate::dataview;
+use std::collections::HashMap;
+
+// Plot a Line Chart:
+// Each series is a list of y values and x is computed
+// from the index of the value: x = x_start + index * x_step
+#[derive(Default)]
+pub struct Line {
+    // Level of detail pyramid of each series
+    lods: HashMap<String, Lod>,
+}
+
+impl Line {
+    fn start(file: &dataview::File) -> f64 {
+        file.dataview.x_start.unwrap_or(0.0)
+    }
+
+    fn step(file: &dataview::File) -> f64 {
+        file.dataview.x_step.unwrap_or(1.0)
+    }
+}
+
+impl Chart for Line {
+    fn update(&mut self, file: &dataview::File) {
+        let start = Self::start(file);
+        let step = Self::step(file);
+        lod::update(&mut self.lods, file, |data| Points::Line(data, start, step));
+    }
+
+    fn view(&self, file: &dataview::File) -> View {
+        View::line_minmax(file, Self::start(file), Self::step(file))
+            .sanity_check(file, 1)
+            .show_axis()
+            .margin()
+    }
+
+    fn draw(&self, canvas: &Canvas, file: &dataview::File) {
+        let start = Self::start(file);
+        let step = Self::step(file);
+        draw_series(canvas, file, &self.lods, |data| {
+            Points::Line(data, start, step)
+        });
+    }
+}
@@ -1,8 +1,16 @@
+pub mod bar;
+pub mod histogram;
+pub mod line;
 pub mod xy;
 
+pub use ::dataviewer::lod::{self, Points};
+
 use crate::canvas::Canvas;
+use crate::canvas::Tooltip;
 use crate::dataview;
-use crate::utils::PairIterator;
+use crate::utils::{LineIterator, PairIterator};
+use lod::Lod;
+use std::collections::HashMap;
 
 #[derive(Clone, Debug)]
 pub struct View {
@@ -22,10 +30,13 @@ impl View {
         }
     }
 
-    pub fn sanity_check(&self, file: &dataview::File) -> Self {
+    /// Fallback to a default view when no series has a point to show,
+    /// a point being made of this number of values in the series data.
+    /// A single point (or a flat series) is shown in the middle of the view.
+    pub fn sanity_check(&self, file: &dataview::File, values: usize) -> Self {
         let mut empty = true;
-        for data in file.data.values() {
-            if data.len() > 2 {
+        for (key, data) in &file.data {
+            if data.len() >= values && !file.is_hidden(key) {
                 empty = false;
             }
         }
@@ -37,14 +48,40 @@ impl View {
                 y_min: -1.0,
                 y_max: 1.0,
             },
-            false => self.clone(),
+            false => {
+                let mut new = self.clone();
+                if new.x_min == new.x_max {
+                    new.x_min -= 1.0;
+                    new.x_max += 1.0;
+                }
+                if new.y_min == new.y_max {
+                    new.y_min -= 1.0;
+                    new.y_max += 1.0;
+                }
+                new
+            }
         }
     }
 
     pub fn xy_minmax(file: &dataview::File) -> Self {
+        Self::minmax(file, PairIterator::new)
+    }
+
+    pub fn line_minmax(file: &dataview::File, start: f64, step: f64) -> Self {
+        Self::minmax(file, |data| LineIterator::new(data, start, step))
+    }
+
+    fn minmax<'a, F, I>(file: &'a dataview::File, points: F) -> Self
+    where
+        F: Fn(&'a [f64]) -> I,
+        I: Iterator<Item = (f64, f64)>,
+    {
         let mut view = Self::new();
-        for data in file.data.values() {
-            let iter = PairIterator::new(data);
+        for (key, data) in &file.data {
+            if file.is_hidden(key) {
+                continue;
+            }
+            let iter = points(data);
             for (x, y) in iter {
                 if x < view.x_min {
                     view.x_min = x;
@@ -91,9 +128,114 @@ impl View {
             y_max: self.y_max + 0.07 * y_range,
         }
     }
+
+    /// Override the computed boundaries with the ones
+    /// explicitly set by the user in the dataview file.
+    pub fn pin(&self, dataview: &dataview::DataView) -> Self {
+        Self {
+            x_min: dataview.x_min.unwrap_or(self.x_min),
+            x_max: dataview.x_max.unwrap_or(self.x_max),
+            y_min: dataview.y_min.unwrap_or(self.y_min),
+            y_max: dataview.y_max.unwrap_or(self.y_max),
+        }
+    }
+}
+
+fn squaredistance(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
+    let x_dt = x2 - x1;
+    let y_dt = y2 - y1;
+    (y_dt * y_dt) + (x_dt * x_dt)
+}
+
+/// Draw each series as a line going through its points,
+/// and the tooltip of the point nearest to the mouse.
+///
+/// points() tells how to access the (x, y) points of a series.
+/// Only the points in the view are drawn, and the series with more points
+/// than pixel columns are decimated using their level of detail pyramid,
+/// without markers. The tooltip only looks at the points near the mouse.
+pub fn draw_series<'a, F>(
+    canvas: &Canvas,
+    file: &'a dataview::File,
+    lods: &'a HashMap<String, Lod>,
+    points: F,
+) where
+    F: Fn(&'a [f64]) -> Points<'a>,
+{
+    let mut tooltip = None;
+    let mut tooltip_distance = 200.0;
+    canvas.draw_axis();
+
+    let view = canvas.view();
+    let columns = canvas.width();
+    let radius = f64::sqrt(tooltip_distance);
+    let mouse_x_min = canvas.x_value(canvas.mouse_x() - radius);
+    let mouse_x_max = canvas.x_value(canvas.mouse_x() + radius);
+    for (key, data) in &file.data {
+        let color = canvas.color(key);
+        canvas.set_color(&color);
+        if file.is_hidden(key) {
+            continue;
+        }
+
+        let lod = match lods.get(key) {
+            Some(lod) => lod,
+            None => {
+                continue;
+            }
+        };
+        let points = points(data);
+        let count = lod.count(&points, view.x_min, view.x_max);
+        let decimate = lod.decimate(count, columns);
+        // Markers are useless when they are denser than the pixels
+        let markers = (count as f64) <= columns;
+
+        let mut previous: Option<lod::Point> = None;
+        let view_columns = decimate.then_some(columns);
+        for point in lod.points(points, view_columns, view.x_min, view.x_max) {
+            // Decimated points are always connected
+            let connected = match previous {
+                Some(previous) => decimate || point.index == previous.index + 1,
+                None => false,
+            };
+            match connected {
+                true => canvas.line_to(point.x, point.y),
+                false => canvas.move_to(point.x, point.y),
+            };
+            if markers {
+                canvas.circle(point.x, point.y, 2.0);
+            }
+            previous = Some(point);
+        }
+        canvas.stroke();
+
+        let mouse_columns = decimate.then_some(2.0 * radius);
+        for point in lod.points(points, mouse_columns, mouse_x_min, mouse_x_max) {
+            let (xpixel, ypixel) = canvas.pixel(point.x, point.y);
+            let distance = squaredistance(xpixel, ypixel, canvas.mouse_x(), canvas.mouse_y());
+            if distance < tooltip_distance {
+                tooltip = Some(Tooltip {
+                    key: key.clone(),
+                    xlabel: None,
+                    x: point.x,
+                    y: point.y,
+                    xpixel,
+                    ypixel,
+                });
+                tooltip_distance = distance;
+            }
+        }
+    }
+
+    if let Some(tooltip) = &tooltip {
+        canvas.draw_tooltip(file, tooltip);
+    }
 }
 
 pub trait Chart {
+    // Notify the Chart that the data of the file have been loaded or updated
+    fn update(&mut self, _file: &dataview::File) {}
+
     fn view(&self, _file: &dataview::File) -> View;
 
     // Draw the Chart
@@ -0,0 +1,311 @@
+use crate::dataview;
+use eyre::{eyre, Result, WrapErr};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Environment variable overriding the default ipc socket path
+pub static SOCKET_ENV: &str = "DATAVIEWER_SOCKET";
+
+/// Default ipc socket path
+static DEFAULT_SOCKET: &str = "/tmp/dataviewer.ipc";
+
+/// Default number of values sent in a single message
+const BATCH_SIZE: usize = 1024;
+
+/// Default maximum delay before sending the pushed values
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Maximum time spent connecting to the viewer or writing a message
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Get the ipc socket path from the environment or the default one
+pub fn default_socket() -> PathBuf {
+    match std::env::var_os(SOCKET_ENV) {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(DEFAULT_SOCKET),
+    }
+}
+
+/// Address of the viewer
+#[derive(Debug, Clone)]
+enum Endpoint {
+    Unix(PathBuf),
+    Tcp(String),
+}
+
+impl Endpoint {
+    fn connect(&self) -> Result<Connection> {
+        let connection = match self {
+            Self::Unix(path) => Connection::Unix(UnixStream::connect(path)?),
+            Self::Tcp(address) => {
+                let mut addresses = address.to_socket_addrs()?;
+                let address = addresses
+                    .next()
+                    .ok_or_else(|| eyre!("No address for {}", address))?;
+                Connection::Tcp(TcpStream::connect_timeout(&address, TIMEOUT)?)
+            }
+        };
+        let reader: Box<dyn Read + Send> = match &connection {
+            Connection::Unix(stream) => {
+                stream.set_write_timeout(Some(TIMEOUT))?;
+                Box::new(stream.try_clone()?)
+            }
+            Connection::Tcp(stream) => {
+                stream.set_write_timeout(Some(TIMEOUT))?;
+                Box::new(stream.try_clone()?)
+            }
+        };
+        std::thread::spawn(move || drain(reader));
+        Ok(connection)
+    }
+}
+
+/// Connection to the viewer, closed when dropped
+enum Connection {
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Unix(stream) => stream.write(buf),
+            Self::Tcp(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Unix(stream) => stream.flush(),
+            Self::Tcp(stream) => stream.flush(),
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // Also stop the thread reading the replies
+        let _ = match self {
+            Self::Unix(stream) => stream.shutdown(Shutdown::Both),
+            Self::Tcp(stream) => stream.shutdown(Shutdown::Both),
+        };
+    }
+}
+
+/// Read the replies of the viewer until the connection is closed,
+/// so they never fill the socket. The viewer only replies errors:
+/// they are reported on the standard error.
+fn drain(mut reader: Box<dyn Read + Send>) {
+    let mut buffer = [0; 4096];
+    let mut reply = vec![];
+    loop {
+        let size = match reader.read(&mut buffer) {
+            Ok(0) | Err(_) => {
+                return;
+            }
+            Ok(size) => size,
+        };
+        for byte in &buffer[..size] {
+            match byte {
+                0 => {
+                    let text = String::from_utf8_lossy(&reply);
+                    eprintln!("The viewer rejected a message:\n{}", text.trim());
+                    reply.clear();
+                }
+                _ => reply.push(*byte),
+            }
+        }
+    }
+}
+
+/// Data update message: only the data (and the stream it targets) are sent
+#[derive(Serialize)]
+struct Update<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<&'a str>,
+    data: &'a HashMap<String, Vec<f64>>,
+}
+
+/// Client of a running viewer, streaming data over its ipc socket.
+///
+/// The pushed values are sent in batches: once BATCH_SIZE values are
+/// pending, or by the first push after BATCH_INTERVAL. There is no timer:
+/// call flush() to send the pending values before an idle period.
+/// If the viewer closes the connection, the client reconnects and declares
+/// its charts again. Values which could not be sent are dropped, and a write
+/// times out after TIMEOUT, so a client neither blocks nor grows without
+/// a viewer. The errors replied by the viewer are reported on stderr.
+///
+/// ```no_run
+/// use dataviewer::{client::Client, dataview};
+///
+/// let mut client = Client::connect_default()?;
+/// let mut file = dataview::File::default();
+/// file.chart.insert("1".into(), dataview::Chart::default());
+/// client.declare(file)?;
+/// client.push("1", 0.0, 1.0)?;
+/// client.flush()?;
+/// # Ok::<(), eyre::Error>(())
+/// ```
+pub struct Client {
+    endpoint: Endpoint,
+    stream: Option<Connection>,
+    /// Charts declared by this client, sent again on reconnection
+    declaration: Option<dataview::File>,
+    /// Values waiting to be sent
+    batch: HashMap<String, Vec<f64>>,
+    batch_len: usize,
+    batch_size: usize,
+    batch_interval: Duration,
+    last_flush: Instant,
+}
+
+impl Client {
+    fn new(endpoint: Endpoint) -> Result<Self> {
+        let stream = endpoint
+            .connect()
+            .wrap_err(format!("Failed to connect to {:?}", &endpoint))?;
+        Ok(Self {
+            endpoint,
+            stream: Some(stream),
+            declaration: None,
+            batch: HashMap::new(),
+            batch_len: 0,
+            batch_size: BATCH_SIZE,
+            batch_interval: BATCH_INTERVAL,
+            last_flush: Instant::now(),
+        })
+    }
+
+    /// Connect to the viewer listening on this ipc socket
+    pub fn connect(path: &Path) -> Result<Self> {
+        Self::new(Endpoint::Unix(path.to_path_buf()))
+    }
+
+    /// Connect to the viewer listening on the default ipc socket
+    pub fn connect_default() -> Result<Self> {
+        Self::connect(&default_socket())
+    }
+
+    /// Connect to the viewer listening on this TCP address (ADDRESS:PORT)
+    pub fn connect_tcp(address: &str) -> Result<Self> {
+        Self::new(Endpoint::Tcp(address.to_string()))
+    }
+
+    /// Send the pushed values once this number of values is reached
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size;
+    }
+
+    /// Send the pushed values by the first push after this interval
+    pub fn set_batch_interval(&mut self, batch_interval: Duration) {
+        self.batch_interval = batch_interval;
+    }
+
+    /// Write a message, reconnecting once if the connection was lost
+    fn send(&mut self, message: &[u8]) -> Result<()> {
+        let mut retry = true;
+        loop {
+            let stream = match self.stream.as_mut() {
+                Some(stream) => stream,
+                None => {
+                    let mut stream = self.endpoint.connect()?;
+                    if let Some(declaration) = &self.declaration {
+                        stream.write_all(&Self::encode(declaration)?)?;
+                    }
+                    self.stream.insert(stream)
+                }
+            };
+            let result = stream.write_all(message).and_then(|_| stream.flush());
+            match result {
+                Ok(()) => {
+                    return Ok(());
+                }
+                Err(e) => {
+                    self.stream = None;
+                    if !retry {
+                        return Err(eyre!(e));
+                    }
+                    retry = false;
+                }
+            }
+        }
+    }
+
+    /// Serialize a message as a NUL terminated TOML message
+    fn encode<T: Serialize>(message: &T) -> Result<Vec<u8>> {
+        let mut bytes = toml::to_string(message)?.into_bytes();
+        bytes.push(0);
+        Ok(bytes)
+    }
+
+    /// Declare the charts (and their initial data) shown by the viewer
+    pub fn declare(&mut self, mut file: dataview::File) -> Result<()> {
+        self.flush()?;
+        // Only the errors are replied: keep the default ipc options
+        file.ipc = None;
+        let message = Self::encode(&file)?;
+        let mut declaration = file;
+        declaration.data.clear();
+        self.declaration = Some(declaration);
+        self.send(&message)
+    }
+
+    /// Push a point to a XY chart
+    pub fn push(&mut self, key: &str, x: f64, y: f64) -> Result<()> {
+        self.push_values(key, &[x, y])
+    }
+
+    /// Push a value to a Line, Bar or Histogram chart
+    pub fn push_value(&mut self, key: &str, value: f64) -> Result<()> {
+        self.push_values(key, &[value])
+    }
+
+    /// Push values to a chart, as x, y pairs for XY charts.
+    /// The values may be kept in the batch until the next push or flush().
+    pub fn push_values(&mut self, key: &str, values: &[f64]) -> Result<()> {
+        match self.batch.get_mut(key) {
+            Some(data) => data.extend(values),
+            None => {
+                self.batch.insert(key.to_string(), values.to_vec());
+            }
+        }
+        self.batch_len += values.len();
+        if self.batch_len >= self.batch_size || self.last_flush.elapsed() >= self.batch_interval {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Send the pushed values now
+    pub fn flush(&mut self) -> Result<()> {
+        self.last_flush = Instant::now();
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let batch = std::mem::take(&mut self.batch);
+        self.batch_len = 0;
+        let update = Update {
+            stream: self
+                .declaration
+                .as_ref()
+                .and_then(|declaration| declaration.stream.as_deref()),
+            data: &batch,
+        };
+        let message = Self::encode(&update)?;
+        self.send(&message)
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            eprintln!("Failed to send the last values to the viewer: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use crate::histogram::Histogram;
+use crate::{dataview, utils};
+use eyre::{eyre, Result};
+use std::path::Path;
+
+/// Options to import a CSV file
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Field delimiter (default: ',' or '\t' for .tsv files)
+    pub delimiter: Option<char>,
+    /// Column used as x, by header name or by number starting at 1
+    /// (default: the first column)
+    pub x_column: Option<String>,
+    /// Columns to plot, by header name or by number starting at 1
+    /// (default: all the columns except x)
+    pub columns: Option<Vec<String>>,
+}
+
+/// Return true if the file is a CSV (or TSV) file
+pub fn is_csv(path: &Path) -> bool {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    matches!(
+        extension
+            .map(|extension| extension.to_lowercase())
+            .as_deref(),
+        Some("csv") | Some("tsv")
+    )
+}
+
+/// Split a CSV line in fields, handling double-quoted fields
+fn split(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => {
+                fields.push(field.trim().to_string());
+                field.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+/// Find a column by header name or by number (starting at 1)
+fn find_column(header: &[String], column: &str) -> Result<usize> {
+    if let Some(i) = header.iter().position(|name| name == column) {
+        return Ok(i);
+    }
+    match column.parse::<usize>() {
+        Ok(i) if i >= 1 && i <= header.len() => Ok(i - 1),
+        _ => Err(eyre!("Unknown column '{}'", column)),
+    }
+}
+
+/// Import CSV text: each column is a series plotted against the x column
+pub fn parse(text: &str, delimiter: char, options: &Options) -> Result<dataview::File> {
+    let mut rows = text
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| split(line, delimiter))
+        .peekable();
+
+    // The first row is a header if any of its fields is not a number
+    let first = rows.peek().ok_or_else(|| eyre!("Empty CSV file"))?;
+    let has_header = first
+        .iter()
+        .any(|field| !field.is_empty() && field.parse::<f64>().is_err());
+    let header: Vec<String> = match has_header {
+        true => rows.next().unwrap(),
+        false => (1..=first.len()).map(|i| format!("Column {}", i)).collect(),
+    };
+
+    let x = match &options.x_column {
+        Some(column) => find_column(&header, column)?,
+        None => 0,
+    };
+    let columns = match &options.columns {
+        Some(columns) => columns
+            .iter()
+            .map(|column| find_column(&header, column))
+            .collect::<Result<Vec<usize>>>()?,
+        None => (0..header.len()).filter(|i| *i != x).collect(),
+    };
+
+    let mut file = dataview::File::default();
+    file.dataview.x_title = Some(header[x].clone());
+    for (i, column) in columns.iter().enumerate() {
+        let key = (i + 1).to_string();
+        let chart = dataview::Chart {
+            title: Some(header[*column].clone()),
+            ..Default::default()
+        };
+        file.chart.insert(key.clone(), chart);
+        file.data.insert(key, vec![]);
+    }
+
+    for row in rows {
+        let x = match row.get(x).and_then(|field| field.parse::<f64>().ok()) {
+            Some(x) => x,
+            None => {
+                continue;
+            }
+        };
+        for (i, column) in columns.iter().enumerate() {
+            let y = match row.get(*column).and_then(|field| field.parse::<f64>().ok()) {
+                Some(y) => y,
+                None => {
+                    continue;
+                }
+            };
+            let data = file.data.get_mut(&(i + 1).to_string()).unwrap();
+            data.push(x);
+            data.push(y);
+        }
+    }
+
+    Ok(file)
+}
+
+/// Import a CSV file as a XY dataview
+pub fn read(path: &Path, options: &Options) -> Result<dataview::File> {
+    let text = std::fs::read_to_string(path)?;
+    let tsv = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("tsv"));
+    let delimiter = match options.delimiter {
+        Some(delimiter) => delimiter,
+        None if tsv => '\t',
+        None => ',',
+    };
+    parse(&text, delimiter, options)
+}
+
+/// Read a dataview file from disk, importing it if it is a CSV file
+pub fn read_file(path: &Path, options: &Options) -> Result<dataview::File> {
+    match is_csv(path) {
+        true => read(path, options),
+        false => dataview::File::read(path),
+    }
+}
+
+/// Quote a CSV field if it contains a delimiter, a quote or a new line
+fn quote(field: &str) -> String {
+    match field.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+/// Points of each series, as shown by the chart, with their title.
+/// Histograms are written as their bins: bin center and height.
+fn series(file: &dataview::File) -> Vec<(String, Vec<(f64, f64)>)> {
+    let mut histogram = Histogram::default();
+    if file.dataview.r#type == dataview::Type::Histogram {
+        histogram.update(file);
+    }
+    let density = file.dataview.density.unwrap_or(false);
+    let mut series = vec![];
+    for (key, data) in &file.data {
+        let title = file
+            .chart
+            .get(key)
+            .and_then(|chart| chart.title.clone())
+            .unwrap_or_else(|| key.clone());
+        let points = match file.dataview.r#type {
+            dataview::Type::XY => utils::PairIterator::new(data).collect(),
+            dataview::Type::Line => {
+                let start = file.dataview.x_start.unwrap_or(0.0);
+                let step = file.dataview.x_step.unwrap_or(1.0);
+                utils::LineIterator::new(data, start, step).collect()
+            }
+            dataview::Type::Bar => utils::LineIterator::new(data, 0.0, 1.0).collect(),
+            dataview::Type::Histogram => histogram
+                .bins(key, density)
+                .map(|bin| (bin.center(), bin.height))
+                .collect(),
+        };
+        series.push((title, points));
+    }
+    series
+}
+
+/// Export a dataview to CSV text.
+/// Each series has its own x column, unless the series are merged
+/// on their x values in a single x column.
+pub fn write(file: &dataview::File, merged: bool) -> String {
+    let series = series(file);
+    let x_title = file.dataview.x_title.as_deref().unwrap_or("x");
+
+    // Bar chart categories are written instead of their index
+    let x_label = |x: f64| match &file.dataview.categories {
+        Some(categories) if file.dataview.r#type == dataview::Type::Bar => categories
+            .get(x as usize)
+            .map(|category| quote(category))
+            .unwrap_or_else(|| x.to_string()),
+        _ => x.to_string(),
+    };
+
+    let mut header = vec![];
+    let mut rows: Vec<Vec<String>> = vec![];
+    match merged {
+        true => {
+            header.push(quote(x_title));
+            // A series may have several samples with the same x:
+            // the n-th sample of each series at x goes in the n-th row of x.
+            let mut cells = vec![];
+            for (i, (title, points)) in series.iter().enumerate() {
+                header.push(quote(title));
+                let mut points = points.clone();
+                points.sort_by(|a, b| a.0.total_cmp(&b.0));
+                let mut nth = 0;
+                for (j, (x, y)) in points.iter().enumerate() {
+                    nth = match j > 0 && points[j - 1].0.total_cmp(x).is_eq() {
+                        true => nth + 1,
+                        false => 0,
+                    };
+                    cells.push((*x, nth, i, *y));
+                }
+            }
+            cells.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+            let mut last = None;
+            for (x, nth, i, y) in cells {
+                // Same bits is the same x for total_cmp
+                if last != Some((x.to_bits(), nth)) {
+                    let mut row = vec![String::new(); series.len() + 1];
+                    row[0] = x_label(x);
+                    rows.push(row);
+                    last = Some((x.to_bits(), nth));
+                }
+                rows.last_mut().unwrap()[i + 1] = y.to_string();
+            }
+        }
+        false => {
+            let count = series.iter().map(|(_, points)| points.len()).max();
+            rows = vec![vec![String::new(); series.len() * 2]; count.unwrap_or(0)];
+            for (i, (title, points)) in series.iter().enumerate() {
+                header.push(quote(&format!("{} {}", title, x_title)));
+                header.push(quote(title));
+                for (row, (x, y)) in points.iter().enumerate() {
+                    rows[row][i * 2] = x_label(*x);
+                    rows[row][i * 2 + 1] = y.to_string();
+                }
+            }
+        }
+    }
+
+    let mut text = header.join(",") + "\n";
+    for row in rows {
+        text += &row.join(",");
+        text += "\n";
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A dataview with a titled series for each (title, data)
+    fn file(r#type: dataview::Type, series: &[(&str, &[f64])]) -> dataview::File {
+        let mut file = dataview::File::default();
+        file.dataview.r#type = r#type;
+        for (i, (title, data)) in series.iter().enumerate() {
+            let key = (i + 1).to_string();
+            let chart = dataview::Chart {
+                title: Some(title.to_string()),
+                ..Default::default()
+            };
+            file.chart.insert(key.clone(), chart);
+            file.data.insert(key, data.to_vec());
+        }
+        file
+    }
+
+    #[test]
+    fn parse_header() {
+        let text = "time,a,b\n0,1,2\n# comment\n1,3,\n";
+        let file = parse(text, ',', &Options::default()).unwrap();
+        assert_eq!(file.dataview.x_title.as_deref(), Some("time"));
+        assert_eq!(file.chart["1"].title.as_deref(), Some("a"));
+        assert_eq!(file.chart["2"].title.as_deref(), Some("b"));
+        assert_eq!(file.data["1"], vec![0.0, 1.0, 1.0, 3.0]);
+        assert_eq!(file.data["2"], vec![0.0, 2.0]);
+    }
+
+    #[test]
+    fn parse_without_header() {
+        let file = parse("1;2\n3;4\n", ';', &Options::default()).unwrap();
+        assert_eq!(file.dataview.x_title.as_deref(), Some("Column 1"));
+        assert_eq!(file.chart["1"].title.as_deref(), Some("Column 2"));
+        assert_eq!(file.data["1"], vec![1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn parse_columns() {
+        let options = Options {
+            x_column: Some("b".to_string()),
+            columns: Some(vec!["1".to_string()]),
+            ..Default::default()
+        };
+        let file = parse("a,b,c\n1,2,3\n4,5,6\n", ',', &options).unwrap();
+        assert_eq!(file.chart.len(), 1);
+        assert_eq!(file.chart["1"].title.as_deref(), Some("a"));
+        assert_eq!(file.data["1"], vec![2.0, 1.0, 5.0, 4.0]);
+
+        let options = Options {
+            x_column: Some("d".to_string()),
+            ..Default::default()
+        };
+        assert!(parse("a,b,c\n1,2,3\n", ',', &options).is_err());
+        assert!(parse("\n\n", ',', &Options::default()).is_err());
+    }
+
+    #[test]
+    fn parse_quoted() {
+        let file = parse("x,\"a, \"\"b\"\"\"\n1,2\n", ',', &Options::default()).unwrap();
+        assert_eq!(file.chart["1"].title.as_deref(), Some("a, \"b\""));
+        assert_eq!(file.data["1"], vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn write_columns() {
+        let file = file(
+            dataview::Type::XY,
+            &[("a", &[0.0, 1.0, 1.0, 2.0]), ("b, c", &[5.0, 6.0])],
+        );
+        let text = write(&file, false);
+        assert_eq!(text, "a x,a,\"b, c x\",\"b, c\"\n0,1,5,6\n1,2,,\n");
+    }
+
+    #[test]
+    fn write_merged() {
+        let file = file(
+            dataview::Type::XY,
+            &[("a", &[0.0, 1.0, 2.0, 2.0]), ("b", &[1.0, 3.0, 2.0, 4.0])],
+        );
+        let text = write(&file, true);
+        assert_eq!(text, "x,a,b\n0,1,\n1,,3\n2,2,4\n");
+    }
+
+    #[test]
+    fn write_merged_repeated_x() {
+        let file = file(
+            dataview::Type::XY,
+            &[
+                ("a", &[1.0, 2.0, 0.0, 1.0, 1.0, 3.0]),
+                ("b", &[1.0, 4.0, 2.0, 5.0]),
+            ],
+        );
+        let text = write(&file, true);
+        assert_eq!(text, "x,a,b\n0,1,\n1,2,4\n1,3,\n2,,5\n");
+    }
+
+    #[test]
+    fn write_merged_signed_zero() {
+        let file = file(
+            dataview::Type::XY,
+            &[
+                ("a", &[-0.0, 1.0]),
+                ("b", &[0.0, 2.0]),
+                ("c", &[f64::NAN, 3.0]),
+            ],
+        );
+        let text = write(&file, true);
+        assert_eq!(text, "x,a,b,c\n-0,1,,\n0,,2,\nNaN,,,3\n");
+    }
+
+    #[test]
+    fn write_line() {
+        let mut file = file(dataview::Type::Line, &[("a", &[1.0, 2.0])]);
+        file.dataview.x_start = Some(10.0);
+        file.dataview.x_step = Some(0.5);
+        assert_eq!(write(&file, false), "a x,a\n10,1\n10.5,2\n");
+    }
+
+    #[test]
+    fn write_histogram_bins() {
+        let mut file = file(dataview::Type::Histogram, &[("a", &[0.5, 1.5, 1.2])]);
+        file.dataview.bin_width = Some(1.0);
+        assert_eq!(write(&file, true), "x,a\n0.5,1\n1.5,2\n");
+    }
+
+    #[test]
+    fn write_then_parse() {
+        let file = file(
+            dataview::Type::XY,
+            &[("a", &[0.0, 1.0, 2.0, 2.5]), ("b", &[0.0, -1.0, 2.0, 1e-3])],
+        );
+        let parsed = parse(&write(&file, true), ',', &Options::default()).unwrap();
+        assert_eq!(parsed.data, file.data);
+        assert_eq!(parsed.chart, file.chart);
+    }
+}
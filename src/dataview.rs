@@ -1,13 +1,29 @@
+/// Definition of the DataView File Format
+use indexmap::IndexMap;
 use serde::Deserialize;
 use serde::Serialize;
-/// Definition of the DataView File Format
-use std::collections::HashMap;
+use std::path::Path;
+
+/// Position of the legend in the chart
+#[derive(Debug, PartialEq, Default, Copy, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Legend {
+    #[default]
+    TopRight,
+    TopLeft,
+    BottomRight,
+    BottomLeft,
+    /// Do not draw the legend
+    None,
+}
 
 #[derive(Debug, PartialEq, Default, Copy, Clone, Deserialize, Serialize)]
 pub enum Type {
     #[default]
     XY,
     Line,
+    Bar,
+    Histogram,
 }
 
 #[derive(Debug, PartialEq, Default, Clone, Deserialize, Serialize)]
@@ -22,6 +38,24 @@ pub struct DataView {
     pub x_max: Option<f64>,
     pub y_min: Option<f64>,
     pub y_max: Option<f64>,
+    /// Line chart: x value of the first sample (default: 0)
+    pub x_start: Option<f64>,
+    /// Line chart: x distance between two samples (default: 1)
+    pub x_step: Option<f64>,
+    /// Bar chart: name of each category
+    pub categories: Option<Vec<String>>,
+    /// Histogram: number of bins (default: 20)
+    pub bins: Option<usize>,
+    /// Histogram: width of a bin (takes precedence over bins)
+    pub bin_width: Option<f64>,
+    /// Histogram: normalize the bins to a probability density
+    pub density: Option<bool>,
+    /// Draw grid lines at the axis ticks
+    pub major_grid: Option<bool>,
+    /// Draw grid lines between the axis ticks
+    pub minor_grid: Option<bool>,
+    /// Position of the legend (default: top-right)
+    pub legend: Option<Legend>,
     pub description: Option<String>,
 }
 
@@ -29,17 +63,75 @@ pub struct DataView {
 pub struct Chart {
     pub title: Option<String>,
     pub description: Option<String>,
+    /// The series is not drawn (toggled by clicking on the legend)
+    pub hidden: Option<bool>,
+}
+
+/// Encoding of the messages of an IPC connection
+#[derive(Debug, PartialEq, Default, Copy, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// TOML messages terminated by a NUL character
+    #[default]
+    Toml,
+    /// JSON messages, each ending with its closing brace (e.g. NDJSON)
+    Json,
+    /// Length-prefixed binary data frames (see ipc::Frame)
+    Binary,
+}
+
+/// IPC only: options of the connection
+#[derive(Debug, PartialEq, Default, Clone, Deserialize, Serialize)]
+pub struct Ipc {
+    /// Reply with an acknowledgement to each message (errors are always replied)
+    #[serde(default)]
+    pub ack: bool,
+    /// Switch the encoding of the next messages and replies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<Encoding>,
 }
 
 /// The root definition of a DataView File
 #[derive(Debug, PartialEq, Default, Clone, Deserialize, Serialize)]
 pub struct File {
+    /// IPC only: name of the stream (tab) targeted by the message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<String>,
+
+    /// IPC only: options of the connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipc: Option<Ipc>,
+
     #[serde(default)]
     pub dataview: DataView,
 
+    /// Series are kept in the order of the file
     #[serde(default)]
-    pub chart: HashMap<String, Chart>,
+    pub chart: IndexMap<String, Chart>,
 
     #[serde(default)]
-    pub data: HashMap<String, Vec<f64>>,
+    pub data: IndexMap<String, Vec<f64>>,
+}
+
+impl File {
+    /// Return true if the series is hidden
+    pub fn is_hidden(&self, key: &str) -> bool {
+        self.chart
+            .get(key)
+            .and_then(|chart| chart.hidden)
+            .unwrap_or(false)
+    }
+
+    /// Read a DataView File from disk, as JSON for .json files or TOML otherwise
+    pub fn read(path: &Path) -> eyre::Result<Self> {
+        let string = std::fs::read_to_string(path)?;
+        let json = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+        let file = match json {
+            true => serde_json::from_str(&string)?,
+            false => toml::from_str(&string)?,
+        };
+        Ok(file)
+    }
 }
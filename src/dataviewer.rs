@@ -1,11 +1,90 @@
-use crate::canvas::Canvas;
+use crate::canvas::{Canvas, Color, Palette};
 use crate::chart::*;
-use crate::dataview;
+use crate::{csv, dataview};
 use eyre::{eyre, Result};
 use gtk::cairo;
 use gtk::glib::source;
 use gtk::prelude::*;
 use gtk4 as gtk;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Image formats supported for export
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ExportFormat {
+    Png,
+    Svg,
+    Pdf,
+}
+
+impl ExportFormat {
+    pub const ALL: [Self; 3] = [Self::Png, Self::Svg, Self::Pdf];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Svg => "svg",
+            Self::Pdf => "pdf",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Png => "PNG",
+            Self::Svg => "SVG",
+            Self::Pdf => "PDF",
+        }
+    }
+
+    /// Guess the format from the file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+}
+
+/// Data formats supported for save
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum SaveFormat {
+    Toml,
+    Json,
+    /// One x column per series
+    Csv,
+    /// Series merged on a single x column
+    CsvMerged,
+}
+
+impl SaveFormat {
+    pub const ALL: [Self; 4] = [Self::Toml, Self::Json, Self::Csv, Self::CsvMerged];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Toml => "toml",
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::CsvMerged => "csv",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Toml => "DataView TOML",
+            Self::Json => "DataView JSON",
+            Self::Csv => "CSV (one x column per series)",
+            Self::CsvMerged => "CSV (series merged on x)",
+        }
+    }
+
+    /// Guess the format from the file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+}
 
 pub struct DataViewer {
     file: dataview::File,
@@ -19,6 +98,11 @@ pub struct DataViewer {
     redraw_timer: Option<source::SourceId>,
     draw_area: Option<gtk::DrawingArea>,
     autoview: bool,
+    // Area of each legend entry, as drawn the last time
+    legend: Vec<(String, (f64, f64, f64, f64))>,
+    // Color of each series, kept for the lifetime of the viewer
+    palette: Palette,
+    colors: HashMap<String, Color>,
 }
 
 impl DataViewer {
@@ -35,6 +119,9 @@ impl DataViewer {
             redraw_timer: None,
             draw_area: None,
             autoview: true,
+            legend: vec![],
+            palette: Palette::palette1(),
+            colors: HashMap::new(),
         }
     }
 
@@ -43,44 +130,145 @@ impl DataViewer {
 
         println!("load: {:?}", self.file);
 
+        // Order the series as their charts are declared
+        let mut data = std::mem::take(&mut self.file.data);
         for key in self.file.chart.keys() {
-            if self.file.data.get(key).is_none() {
-                self.file.data.insert(key.clone(), vec![]);
+            let values = data.shift_remove(key).unwrap_or_default();
+            self.file.data.insert(key.clone(), values);
+        }
+        self.file.data.extend(data);
+
+        // Bind a color to each new series
+        for key in self.file.data.keys() {
+            if !self.colors.contains_key(key) {
+                self.colors.insert(key.clone(), self.palette.next());
             }
         }
 
         //println!("file: {:?}", file);
-        let chart = match self.file.dataview.r#type {
-            dataview::Type::XY => Box::new(xy::XY),
-            r#type => {
-                return Err(eyre!("Unimplemented format '{:?}'", r#type));
-            }
+        let mut chart: Box<dyn Chart> = match self.file.dataview.r#type {
+            dataview::Type::XY => Box::new(xy::XY::default()),
+            dataview::Type::Line => Box::new(line::Line::default()),
+            dataview::Type::Bar => Box::new(bar::Bar),
+            dataview::Type::Histogram => Box::new(histogram::Histogram::default()),
         };
-        self.view = chart.view(&self.file);
+        chart.update(&self.file);
         self.chart = Some(chart);
+        self.view = self.compute_view();
         Ok(())
     }
 
-    pub fn save_as(&self, path: &std::path::Path) -> Result<()> {
-        let toml = toml::to_string(&self.file)?;
-        std::fs::write(path, toml)?;
+    /// Replace the file, keeping the current view
+    /// if the user moved away from the autoview.
+    pub fn reload(&mut self, file: dataview::File) -> Result<()> {
+        let view = self.view.clone();
+        self.load(file)?;
+        if !self.autoview {
+            self.view = view;
+        }
+        self.queue_redraw();
         Ok(())
     }
 
-    pub fn export_as_png(&mut self, area: &gtk::DrawingArea, path: &std::path::Path) -> Result<()> {
-        let width = self.width as i32;
-        let height = self.height as i32;
-        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)?;
-        let cairo = cairo::Context::new(&surface)?;
+    /// Compute the view from the data, honoring the boundaries
+    /// pinned in the [dataview] table.
+    fn compute_view(&self) -> View {
+        match &self.chart {
+            Some(chart) => chart.view(&self.file).pin(&self.file.dataview),
+            None => View::new(),
+        }
+    }
+
+    pub fn file(&self) -> &dataview::File {
+        &self.file
+    }
+
+    pub fn save_as(&self, path: &Path, format: SaveFormat) -> Result<()> {
+        // Save the current zoom if the user moved away from the autoview
+        let mut file = self.file.clone();
+        if !self.autoview {
+            file.dataview.x_min = Some(self.view.x_min);
+            file.dataview.x_max = Some(self.view.x_max);
+            file.dataview.y_min = Some(self.view.y_min);
+            file.dataview.y_max = Some(self.view.y_max);
+        }
+        let text = match format {
+            SaveFormat::Toml => toml::to_string(&file)?,
+            SaveFormat::Json => {
+                // JSON has no NaN nor infinity: they would be written as null
+                // and the file could not be loaded back.
+                let non_finite = file.data.iter().find_map(|(key, data)| {
+                    let value = data.iter().find(|value| !value.is_finite())?;
+                    Some((key, value))
+                });
+                if let Some((key, value)) = non_finite {
+                    return Err(eyre!(
+                        "Series '{}' has a {} value which can not be saved as JSON, save it as TOML",
+                        key,
+                        value
+                    ));
+                }
+                serde_json::to_string_pretty(&file)?
+            }
+            SaveFormat::Csv => csv::write(&file, false),
+            SaveFormat::CsvMerged => csv::write(&file, true),
+        };
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Draw the chart on a surface of the given size
+    fn render(&mut self, surface: &cairo::Surface, width: i32, height: i32) -> Result<()> {
+        let cairo = cairo::Context::new(surface)?;
         cairo.set_source_rgb(1.0, 1.0, 1.0);
-        cairo.fill()?;
         cairo.paint()?;
-        self.draw(area, &cairo, width, height);
+        let (old_width, old_height) = (self.width, self.height);
+        let old_legend = std::mem::take(&mut self.legend);
+        self.draw(&cairo, width, height);
+        self.width = old_width;
+        self.height = old_height;
+        self.legend = old_legend;
+        Ok(())
+    }
+
+    pub fn export_as_png(&mut self, path: &Path, width: i32, height: i32) -> Result<()> {
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+        self.render(&surface, width, height)?;
         let mut file = std::fs::File::create(path)?;
         surface.write_to_png(&mut file)?;
         Ok(())
     }
 
+    pub fn export_as_svg(&mut self, path: &Path, width: i32, height: i32) -> Result<()> {
+        let surface = cairo::SvgSurface::new(width.into(), height.into(), Some(path))?;
+        self.render(&surface, width, height)?;
+        surface.finish();
+        surface.status()?;
+        Ok(())
+    }
+
+    pub fn export_as_pdf(&mut self, path: &Path, width: i32, height: i32) -> Result<()> {
+        let surface = cairo::PdfSurface::new(width.into(), height.into(), path)?;
+        self.render(&surface, width, height)?;
+        surface.finish();
+        surface.status()?;
+        Ok(())
+    }
+
+    pub fn export(
+        &mut self,
+        path: &Path,
+        format: ExportFormat,
+        width: i32,
+        height: i32,
+    ) -> Result<()> {
+        match format {
+            ExportFormat::Png => self.export_as_png(path, width, height),
+            ExportFormat::Svg => self.export_as_svg(path, width, height),
+            ExportFormat::Pdf => self.export_as_pdf(path, width, height),
+        }
+    }
+
     pub fn update(&mut self, update: dataview::File) {
         for (key, value) in update.data {
             let data = self.file.data.get_mut(&key);
@@ -92,20 +280,16 @@ impl DataViewer {
             };
             data.extend(value);
         }
+        if let Some(chart) = self.chart.as_mut() {
+            chart.update(&self.file);
+        }
         if self.autoview {
-            self.view = self.chart.as_ref().unwrap().view(&self.file);
+            self.view = self.compute_view();
         }
         self.queue_redraw();
     }
 
-    pub fn draw(
-        &mut self,
-        area: &gtk::DrawingArea,
-        cairo: &cairo::Context,
-        width: i32,
-        height: i32,
-    ) {
-        self.draw_area = Some(area.clone());
+    pub fn draw(&mut self, cairo: &cairo::Context, width: i32, height: i32) {
         self.width = width.into();
         self.height = height.into();
         let chart = match &self.chart {
@@ -114,8 +298,7 @@ impl DataViewer {
                 return;
             }
         };
-        let canvas = Canvas::new(
-            area,
+        let mut canvas = Canvas::new(
             cairo,
             width,
             height,
@@ -123,9 +306,17 @@ impl DataViewer {
             self.mouse_yref,
             &self.view,
         );
+        canvas.set_grid(&self.file.dataview);
+        canvas.set_colors(&self.colors);
         cairo.set_source_rgb(0.0, 0.0, 0.0);
         chart.draw(&canvas, &self.file);
         canvas.draw(&self.file);
+        self.legend = canvas.draw_legend(&self.file);
+    }
+
+    /// Set the DrawingArea to redraw when the view or the data change
+    pub fn set_draw_area(&mut self, draw_area: &gtk::DrawingArea) {
+        self.draw_area = Some(draw_area.clone());
     }
 
     pub fn queue_redraw(&self) {
@@ -148,7 +339,28 @@ impl DataViewer {
         self.autoview = false;
     }
 
+    /// Show or hide a series
+    pub fn toggle_series(&mut self, key: &str) {
+        // Series without a [chart] table are shown in the legend too
+        let chart = self.file.chart.entry(key.to_string()).or_default();
+        chart.hidden = Some(!chart.hidden.unwrap_or(false));
+        if self.autoview {
+            self.view = self.compute_view();
+        }
+        self.queue_redraw();
+    }
+
     pub fn mouse_clicked(&mut self, x: f64, y: f64) {
+        // A click on a legend entry toggles its series
+        let entry = self.legend.iter().find(|(_, (left, top, right, bottom))| {
+            x >= *left && x <= *right && y >= *top && y <= *bottom
+        });
+        if let Some((key, _)) = entry {
+            let key = key.clone();
+            self.toggle_series(&key);
+            return;
+        }
+
         self.mouse_is_pressed = true;
         self.mouse_xref = x;
         self.mouse_yref = y;
@@ -204,10 +416,8 @@ impl DataViewer {
         self.autoview = autoview;
 
         if self.autoview {
-            if let Some(chart) = self.chart.as_ref() {
-                self.view = chart.view(&self.file);
-                self.queue_redraw();
-            }
+            self.view = self.compute_view();
+            self.queue_redraw();
         }
     }
 }
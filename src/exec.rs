@@ -0,0 +1,183 @@
+use crate::*;
+use eyre::{eyre, Result};
+use gtk::{gio, glib};
+use gtk4 as gtk;
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Delay before killing a command which does not exit once stopped
+const KILL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A running command, in its own process group
+struct Child {
+    subprocess: gio::Subprocess,
+    /// Process group of the command and of the processes it spawned
+    pgid: libc::pid_t,
+    /// Task reading the output of the command into the tab
+    reader: glib::JoinHandle<()>,
+}
+
+/// Send a signal to a process group, return false if it is gone
+fn signal(pgid: libc::pid_t, signal: libc::c_int) -> bool {
+    unsafe { libc::kill(-pgid, signal) == 0 }
+}
+
+impl Child {
+    /// Ask the processes of the command to terminate,
+    /// and kill them if they are still running after KILL_TIMEOUT.
+    fn stop(&self) {
+        let pgid = self.pgid;
+        if !signal(pgid, libc::SIGTERM) {
+            return;
+        }
+        glib::timeout_add_local_once(KILL_TIMEOUT, move || {
+            if signal(pgid, 0) {
+                signal(pgid, libc::SIGKILL);
+            }
+        });
+    }
+}
+
+/// A command whose standard output is read as a stream of messages
+/// and shown in its own tab. The tab shows the status of the command
+/// and allows to stop or restart it.
+struct Exec {
+    app: gtk::Application,
+    window: gtk::Window,
+    draw_area: gtk::DrawingArea,
+    launcher: gio::SubprocessLauncher,
+    command: String,
+    status: gtk::Label,
+    child: RefCell<Option<Child>>,
+}
+
+impl Exec {
+    /// Spawn the command and feed its output to the tab
+    fn start(self: &Rc<Self>) -> Result<()> {
+        let argv = ["/bin/sh", "-c", &self.command].map(std::ffi::OsStr::new);
+        let subprocess = self.launcher.spawn(&argv)?;
+        let stdout = subprocess.stdout_pipe().unwrap();
+        let pgid = subprocess
+            .identifier()
+            .and_then(|pid| pid.parse().ok())
+            .ok_or_else(|| eyre!("Failed to get the pid of {}", self.command))?;
+        self.status.set_text("running");
+
+        let exec = self.clone();
+        let subprocess_ref = subprocess.clone();
+        let main_context = glib::MainContext::default();
+        let reader = main_context.spawn_local(async move {
+            let stream = stream::Stream::new(stdout, None);
+            server::serve_draw_area(
+                &exec.app,
+                &exec.window,
+                &exec.draw_area,
+                stream,
+                &exec.command,
+            )
+            .await;
+
+            if let Err(e) = subprocess.wait_future().await {
+                println!("Failed to wait for {}: {:?}", exec.command, e);
+            }
+            let status = match subprocess.has_signaled() {
+                true => format!("killed by signal {}", subprocess.term_sig()),
+                false => format!("exited with code {}", subprocess.exit_status()),
+            };
+            println!("{}: {}", exec.command, status);
+
+            // The command may have been restarted in the meantime
+            let running =
+                exec.child.borrow().as_ref().map(|child| &child.subprocess) == Some(&subprocess);
+            if running {
+                exec.status.set_text(&status);
+            }
+        });
+        self.child.replace(Some(Child {
+            subprocess: subprocess_ref,
+            pgid,
+            reader,
+        }));
+        Ok(())
+    }
+
+    fn stop(&self) {
+        if let Some(child) = self.child.borrow().as_ref() {
+            child.stop();
+        }
+    }
+
+    /// Stop the command and start it again: the output of the previous
+    /// command is not read anymore, even if it is still running.
+    fn restart(self: &Rc<Self>) -> Result<()> {
+        if let Some(child) = self.child.take() {
+            child.stop();
+            child.reader.abort();
+        }
+        self.start()
+    }
+}
+
+/// Run a shell command in a new tab of this window
+pub fn run(
+    app: &gtk::Application,
+    window: &gtk::Window,
+    command: &str,
+    cwd: &Path,
+) -> Result<gtk::DrawingArea> {
+    let draw_area = window.new_draw_area(dataview::File::default(), command)?;
+
+    // Replace the tab label with the command status and controls
+    let status = gtk::Label::new(None);
+    let stop = gtk::Button::from_icon_name("media-playback-stop-symbolic");
+    stop.set_tooltip_text(Some("Stop"));
+    let restart = gtk::Button::from_icon_name("view-refresh-symbolic");
+    restart.set_tooltip_text(Some("Restart"));
+    let tab = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    tab.append(&gtk::Label::new(Some(command)));
+    tab.append(&status);
+    tab.append(&stop);
+    tab.append(&restart);
+    window.get_notebook().set_tab_label(&draw_area, Some(&tab));
+
+    let launcher = gio::SubprocessLauncher::new(gio::SubprocessFlags::STDOUT_PIPE);
+    launcher.set_cwd(cwd);
+    // Run the command in its own process group, to stop its children too
+    launcher.set_child_setup(|| unsafe {
+        libc::setpgid(0, 0);
+    });
+    let exec = Rc::new(Exec {
+        app: app.clone(),
+        window: window.clone(),
+        draw_area: draw_area.clone(),
+        launcher,
+        command: command.to_string(),
+        status,
+        child: RefCell::new(None),
+    });
+
+    let exec_ref = exec.clone();
+    stop.connect_clicked(move |_| {
+        exec_ref.stop();
+    });
+
+    let exec_ref = exec.clone();
+    restart.connect_clicked(move |_| {
+        if let Err(e) = exec_ref.restart() {
+            let e = e.wrap_err(format!("Failed to restart {}", exec_ref.command));
+            exec_ref.window.error_bar(&format!("{:?}", e));
+        }
+    });
+
+    // The command does not outlive its window
+    let exec_ref = exec.clone();
+    window.connect_close_request(move |_| {
+        exec_ref.stop();
+        glib::signal::Propagation::Proceed
+    });
+
+    exec.start()?;
+    Ok(draw_area)
+}
@@ -0,0 +1,77 @@
+use crate::dataviewer::{DataViewer, ExportFormat};
+use crate::{args, csv};
+use eyre::{eyre, Result, WrapErr};
+use std::path::PathBuf;
+
+static USAGE: &str =
+    "Usage: dataviewer export INPUT -o OUTPUT.{png,svg,pdf} [--width WIDTH] [--height HEIGHT]";
+
+/// Headless export of a dataview file to an image.
+/// The image format is chosen from the OUTPUT extension.
+/// It does not require any display.
+struct Export {
+    input: PathBuf,
+    output: PathBuf,
+    width: i32,
+    height: i32,
+}
+
+impl Export {
+    fn parse(args: &[String]) -> Result<Self> {
+        let mut input = None;
+        let mut output = None;
+        let mut width = 900;
+        let mut height = 600;
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "-o" | "--output" => {
+                    output = Some(PathBuf::from(args::value(&mut iter, arg)?));
+                }
+                "--width" => {
+                    let value = args::value(&mut iter, arg)?;
+                    width = value
+                        .parse()
+                        .wrap_err(format!("Invalid width '{}'", value))?;
+                }
+                "--height" => {
+                    let value = args::value(&mut iter, arg)?;
+                    height = value
+                        .parse()
+                        .wrap_err(format!("Invalid height '{}'", value))?;
+                }
+                _ => {
+                    if input.is_some() {
+                        return Err(eyre!("Unexpected argument '{}'", arg));
+                    }
+                    input = Some(PathBuf::from(arg));
+                }
+            }
+        }
+
+        Ok(Self {
+            input: input.ok_or_else(|| eyre!("Missing input file"))?,
+            output: output.ok_or_else(|| eyre!("Missing output file"))?,
+            width,
+            height,
+        })
+    }
+}
+
+/// Run the export subcommand with its arguments
+pub fn main(args: &[String]) -> Result<()> {
+    let export = Export::parse(args).wrap_err(USAGE)?;
+    let format = ExportFormat::from_path(&export.output)
+        .ok_or_else(|| eyre!("Unsupported export format for {:?}", &export.output))
+        .wrap_err(USAGE)?;
+    let file = csv::read_file(&export.input, &csv::Options::default())
+        .wrap_err(format!("Failed to open {:?}", &export.input))?;
+
+    let mut dataviewer = DataViewer::new();
+    dataviewer.load(file)?;
+    dataviewer
+        .export(&export.output, format, export.width, export.height)
+        .wrap_err(format!("Failed to export {:?}", &export.output))?;
+    Ok(())
+}
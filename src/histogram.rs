@@ -0,0 +1,385 @@
+//! Binning of the Histogram samples, shared by the Histogram chart
+//! and the CSV export.
+
+use crate::dataview;
+use std::collections::{BTreeMap, HashMap};
+
+// Each series is a flat list of raw samples which are counted in bins.
+// Bins are updated incrementally as new samples are received: when the
+// bins are computed from the samples range and new samples fall out of
+// it, the bins width is doubled by merging the bins two by two.
+// Samples are only counted again from scratch when some are removed
+// or when the bins options change.
+pub struct Histogram {
+    // Left edge of the bin 0
+    origin: f64,
+    // Width of a bin
+    width: f64,
+    // Number of bins when the bins are computed from the samples range
+    bins: Option<i64>,
+    // Range of the samples counted so far
+    min: f64,
+    max: f64,
+    series: HashMap<String, Counts>,
+}
+
+#[derive(Default)]
+struct Counts {
+    // Number of samples of the series already processed
+    samples: usize,
+    // Number of samples counted in the bins
+    total: u64,
+    // Number of samples in each bin, indexed by bin number
+    counts: BTreeMap<i64, u64>,
+    // Number of samples on the right edge of the last bin (and counted in it),
+    // when the number of bins is fixed
+    edge: u64,
+}
+
+/// A non empty bin of a series
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bin {
+    pub left: f64,
+    pub right: f64,
+    /// Number of samples in the bin, or their density
+    pub height: f64,
+}
+
+impl Bin {
+    pub fn center(&self) -> f64 {
+        (self.left + self.right) / 2.0
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            origin: 0.0,
+            width: 1.0,
+            bins: None,
+            min: f64::MAX,
+            max: f64::MIN,
+            series: HashMap::new(),
+        }
+    }
+}
+
+impl Histogram {
+    /// Return the height of a bin
+    fn height(&self, count: u64, total: u64, density: bool) -> f64 {
+        match density && total > 0 {
+            true => (count as f64) / ((total as f64) * self.width),
+            false => count as f64,
+        }
+    }
+
+    /// Return the (left, right) edges of a bin
+    fn edges(&self, bin: i64) -> (f64, f64) {
+        let left = self.origin + (bin as f64) * self.width;
+        (left, left + self.width)
+    }
+
+    /// Return the bin in which a sample is counted,
+    /// and if the sample is on the right edge of the last bin
+    fn bin(&self, sample: f64) -> (i64, bool) {
+        let bin = ((sample - self.origin) / self.width).floor() as i64;
+        match self.bins {
+            // The maximum sample is counted in the last bin
+            Some(bins) if bin >= bins => (bins - 1, true),
+            _ => (bin, false),
+        }
+    }
+
+    /// Return the non empty bins of a series, in increasing order
+    pub fn bins<'a>(&'a self, key: &str, density: bool) -> impl Iterator<Item = Bin> + 'a {
+        self.series.get(key).into_iter().flat_map(move |counts| {
+            counts.counts.iter().map(move |(bin, count)| {
+                let (left, right) = self.edges(*bin);
+                Bin {
+                    left,
+                    right,
+                    height: self.height(*count, counts.total, density),
+                }
+            })
+        })
+    }
+
+    /// Forget the counted samples, to count them again
+    fn clear(&mut self) {
+        for counts in self.series.values_mut() {
+            *counts = Counts::default();
+        }
+        self.min = f64::MAX;
+        self.max = f64::MIN;
+    }
+
+    /// Extend the range of the bins to the new samples from min to max
+    fn extend(&mut self, min: f64, max: f64) {
+        let high = max > self.max;
+        let first = self.min > self.max;
+        self.min = self.min.min(min);
+        self.max = self.max.max(max);
+        let bins = match self.bins {
+            Some(bins) => bins,
+            None => {
+                return;
+            }
+        };
+        if first {
+            let range = match max > min {
+                true => max - min,
+                false => 1.0,
+            };
+            self.origin = min;
+            self.width = range / (bins as f64);
+            return;
+        }
+
+        // The maximum sample may be slightly over the right edge of the bins
+        // because of rounding errors: only check the samples above it.
+        loop {
+            let below = min < self.origin;
+            let above = high && max > self.origin + (bins as f64) * self.width;
+            if !below && !above {
+                return;
+            }
+            // Extend the range to the left by up to bins old bins,
+            // and to the right by the rest.
+            let shift = match below {
+                true => (((self.origin - min) / self.width).ceil() as i64).min(bins),
+                false => 0,
+            };
+            self.origin -= (shift as f64) * self.width;
+            self.width *= 2.0;
+
+            // The old right edge is either still the right edge
+            // or the left edge of a bin
+            let last = (bins - 1 + shift).div_euclid(2);
+            let edge = (bins + shift).div_euclid(2).min(bins - 1);
+            for counts in self.series.values_mut() {
+                let mut merged = BTreeMap::new();
+                for (bin, count) in &counts.counts {
+                    *merged.entry((bin + shift).div_euclid(2)).or_default() += count;
+                }
+                if counts.edge > 0 && edge != last {
+                    let count = merged.get_mut(&last).unwrap();
+                    *count -= counts.edge;
+                    if *count == 0 {
+                        merged.remove(&last);
+                    }
+                    *merged.entry(edge).or_default() += counts.edge;
+                    counts.edge = 0;
+                }
+                counts.counts = merged;
+            }
+        }
+    }
+
+    /// Count the samples received since the last update
+    pub fn update(&mut self, file: &dataview::File) {
+        // Start from scratch if some samples were removed
+        let reloaded = self
+            .series
+            .iter()
+            .any(|(key, counts)| match file.data.get(key) {
+                Some(data) => data.len() < counts.samples,
+                None => true,
+            });
+        if reloaded {
+            self.series.clear();
+            self.clear();
+        }
+
+        // Count the samples again if the bins options changed
+        match file.dataview.bin_width {
+            Some(width) if width > 0.0 => {
+                if self.bins.is_some() || self.origin != 0.0 || self.width != width {
+                    self.clear();
+                    self.origin = 0.0;
+                    self.width = width;
+                    self.bins = None;
+                }
+            }
+            _ => {
+                let bins = file.dataview.bins.unwrap_or(20).max(1) as i64;
+                if self.bins != Some(bins) {
+                    self.clear();
+                    self.bins = Some(bins);
+                }
+            }
+        }
+
+        // Update the range with the new samples
+        let (mut min, mut max) = (f64::MAX, f64::MIN);
+        for (key, data) in &file.data {
+            let samples = self.series.get(key).map_or(0, |counts| counts.samples);
+            for sample in data[samples..].iter().filter(|x| x.is_finite()) {
+                min = min.min(*sample);
+                max = max.max(*sample);
+            }
+        }
+        if min <= max {
+            self.extend(min, max);
+        }
+
+        // Count the samples not yet counted
+        for (key, data) in &file.data {
+            let mut counts = self.series.remove(key).unwrap_or_default();
+            for sample in data[counts.samples..].iter().filter(|x| x.is_finite()) {
+                let (bin, edge) = self.bin(*sample);
+                *counts.counts.entry(bin).or_default() += 1;
+                counts.total += 1;
+                counts.edge += edge as u64;
+            }
+            counts.samples = data.len();
+            self.series.insert(key.clone(), counts);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(data: &[f64], bins: Option<usize>, bin_width: Option<f64>) -> dataview::File {
+        let mut file = dataview::File::default();
+        file.dataview.r#type = dataview::Type::Histogram;
+        file.dataview.bins = bins;
+        file.dataview.bin_width = bin_width;
+        file.data.insert("1".to_string(), data.to_vec());
+        file
+    }
+
+    // (left, right, height) of the bins of the series
+    fn bins(histogram: &Histogram, density: bool) -> Vec<(f64, f64, f64)> {
+        histogram
+            .bins("1", density)
+            .map(|bin| (bin.left, bin.right, bin.height))
+            .collect()
+    }
+
+    #[test]
+    fn binning() {
+        let mut histogram = Histogram::default();
+        histogram.update(&file(&[0.5, 1.5, 1.2, -0.5, f64::NAN], None, Some(1.0)));
+        assert_eq!(
+            bins(&histogram, false),
+            vec![(-1.0, 0.0, 1.0), (0.0, 1.0, 1.0), (1.0, 2.0, 2.0)]
+        );
+
+        // Bins computed from the samples range
+        let mut histogram = Histogram::default();
+        histogram.update(&file(&[0.0, 0.5, 1.0, 1.0, 3.0], Some(4), None));
+        assert_eq!(
+            bins(&histogram, false),
+            vec![(0.0, 0.75, 2.0), (0.75, 1.5, 2.0), (2.25, 3.0, 1.0)]
+        );
+
+        // A single value
+        let mut histogram = Histogram::default();
+        histogram.update(&file(&[2.0, 2.0], Some(4), None));
+        assert_eq!(bins(&histogram, false), vec![(2.0, 2.25, 2.0)]);
+    }
+
+    #[test]
+    fn maximum_in_last_bin() {
+        let mut histogram = Histogram::default();
+        histogram.update(&file(&[0.0, 1.0, 2.0, 3.0, 4.0], Some(4), None));
+        assert_eq!(
+            bins(&histogram, false),
+            vec![
+                (0.0, 1.0, 1.0),
+                (1.0, 2.0, 1.0),
+                (2.0, 3.0, 1.0),
+                (3.0, 4.0, 2.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn density() {
+        let mut histogram = Histogram::default();
+        histogram.update(&file(&[0.0, 1.0, 2.0, 3.0], Some(2), None));
+        assert_eq!(
+            bins(&histogram, true),
+            vec![(0.0, 1.5, 1.0 / 3.0), (1.5, 3.0, 1.0 / 3.0)]
+        );
+        let area: f64 = histogram
+            .bins("1", true)
+            .map(|bin| bin.height * (bin.right - bin.left))
+            .sum();
+        assert_eq!(area, 1.0);
+    }
+
+    #[test]
+    fn grow_range() {
+        let mut data = vec![0.0, 1.0, 2.0, 3.0, 4.0];
+        let mut histogram = Histogram::default();
+        histogram.update(&file(&data, Some(4), None));
+
+        // Samples in the range are counted in the same bins
+        data.push(3.5);
+        histogram.update(&file(&data, Some(4), None));
+        assert_eq!(histogram.width, 1.0);
+
+        // The previous maximum moves out of the last bin
+        data.push(8.0);
+        histogram.update(&file(&data, Some(4), None));
+        assert_eq!(
+            bins(&histogram, false),
+            vec![
+                (0.0, 2.0, 2.0),
+                (2.0, 4.0, 3.0),
+                (4.0, 6.0, 1.0),
+                (6.0, 8.0, 1.0)
+            ]
+        );
+
+        data.push(-4.0);
+        histogram.update(&file(&data, Some(4), None));
+        assert_eq!(
+            bins(&histogram, false),
+            vec![
+                (-4.0, 0.0, 1.0),
+                (0.0, 4.0, 5.0),
+                (4.0, 8.0, 1.0),
+                (8.0, 12.0, 1.0)
+            ]
+        );
+
+        // Several doublings at once
+        data.push(100.0);
+        histogram.update(&file(&data, Some(4), None));
+        assert_eq!(
+            bins(&histogram, false),
+            vec![(-4.0, 28.0, 8.0), (92.0, 124.0, 1.0)]
+        );
+    }
+
+    #[test]
+    fn reload() {
+        let mut histogram = Histogram::default();
+        histogram.update(&file(&[0.0, 1.0, 2.0, 3.0, 4.0], Some(4), None));
+
+        // Removed samples are not counted anymore
+        histogram.update(&file(&[10.0, 11.0], Some(4), None));
+        assert_eq!(
+            bins(&histogram, false),
+            vec![(10.0, 10.25, 1.0), (10.75, 11.0, 1.0)]
+        );
+
+        // New options count the samples again
+        histogram.update(&file(&[10.0, 11.0], Some(1), None));
+        assert_eq!(bins(&histogram, false), vec![(10.0, 11.0, 2.0)]);
+        histogram.update(&file(&[10.0, 11.0], None, Some(2.0)));
+        assert_eq!(bins(&histogram, false), vec![(10.0, 12.0, 2.0)]);
+
+        // Removed series
+        let mut file = file(&[1.0], Some(4), None);
+        file.data.insert("2".to_string(), vec![5.0]);
+        file.data.shift_remove("1");
+        histogram.update(&file);
+        assert!(bins(&histogram, false).is_empty());
+        assert_eq!(histogram.min, 5.0);
+    }
+}
@@ -0,0 +1,405 @@
+//! Messages of the ipc protocol: the errors and replies sent back
+//! to the clients, and the binary data frames.
+
+use crate::dataview;
+use serde::Serialize;
+
+/// Error returned to the client when a message is rejected
+#[derive(Debug)]
+pub enum IpcError {
+    /// The message is not a valid dataview file
+    Parse {
+        message: String,
+        line: Option<usize>,
+        column: Option<usize>,
+    },
+    /// Data refers to a chart which was not declared
+    UnknownChart { key: String },
+    /// Data of a XY chart must be a list of x, y pairs
+    OddLength { key: String, len: usize },
+    /// Data is sent before any chart is declared
+    NoChart,
+    /// The message could not be applied
+    Invalid(eyre::Error),
+}
+
+impl IpcError {
+    pub fn parse(buff: &str, e: toml::de::Error) -> Self {
+        let (line, column) = match e.span() {
+            Some(span) => {
+                let before = &buff[..span.start.min(buff.len())];
+                let line = before.matches('\n').count() + 1;
+                let column = match before.rfind('\n') {
+                    Some(i) => before[i + 1..].chars().count() + 1,
+                    None => before.chars().count() + 1,
+                };
+                (Some(line), Some(column))
+            }
+            None => (None, None),
+        };
+        Self::Parse {
+            message: e.message().to_string(),
+            line,
+            column,
+        }
+    }
+
+    pub fn json(e: serde_json::Error) -> Self {
+        // serde_json appends the position to its message
+        let message = e.to_string();
+        let position = format!(" at line {} column {}", e.line(), e.column());
+        let message = message.strip_suffix(&position).unwrap_or(&message);
+        let (line, column) = match e.line() {
+            0 => (None, None),
+            line => (Some(line), Some(e.column())),
+        };
+        Self::Parse {
+            message: message.to_string(),
+            line,
+            column,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Parse { .. } => "parse",
+            Self::UnknownChart { .. } => "unknown_chart",
+            Self::OddLength { .. } => "odd_length",
+            Self::NoChart => "no_chart",
+            Self::Invalid(_) => "invalid",
+        }
+    }
+}
+
+impl std::fmt::Display for IpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse {
+                message,
+                line: Some(line),
+                column: Some(column),
+            } => write!(
+                f,
+                "Parse error at line {} column {}: {}",
+                line, column, message
+            ),
+            Self::Parse { message, .. } => write!(f, "Parse error: {}", message),
+            Self::UnknownChart { key } => write!(f, "Data for undeclared chart '{}'", key),
+            Self::OddLength { key, len } => write!(
+                f,
+                "Data of chart '{}' has an odd length ({}): expected x, y pairs",
+                key, len
+            ),
+            Self::NoChart => write!(f, "No chart declared for this update"),
+            Self::Invalid(e) => write!(f, "{:?}", e),
+        }
+    }
+}
+
+/// Reply sent back to the client: an acknowledgement or an error
+#[derive(Debug, Default, Serialize)]
+pub struct Reply {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+}
+
+impl Reply {
+    pub fn ack() -> Self {
+        Self {
+            status: "ok",
+            ..Default::default()
+        }
+    }
+
+    pub fn error(e: &IpcError) -> Self {
+        let mut reply = Self {
+            status: "error",
+            error: Some(e.kind()),
+            message: Some(e.to_string()),
+            ..Default::default()
+        };
+        match e {
+            IpcError::Parse { line, column, .. } => {
+                reply.line = *line;
+                reply.column = *column;
+            }
+            IpcError::UnknownChart { key } | IpcError::OddLength { key, .. } => {
+                reply.key = Some(key.clone());
+            }
+            _ => {}
+        }
+        reply
+    }
+
+    /// Serialize the reply as a NUL terminated TOML message
+    /// or as a new line terminated JSON message.
+    /// Binary frames are replied with TOML messages.
+    pub fn to_bytes(&self, encoding: dataview::Encoding) -> Vec<u8> {
+        match encoding {
+            dataview::Encoding::Toml | dataview::Encoding::Binary => {
+                let mut bytes = toml::to_string(self).unwrap_or_default().into_bytes();
+                bytes.push(0);
+                bytes
+            }
+            dataview::Encoding::Json => {
+                let mut bytes = serde_json::to_vec(self).unwrap_or_default();
+                bytes.push(b'\n');
+                bytes
+            }
+        }
+    }
+}
+
+/// Check the data of a message against the charts it is applied to
+pub fn check_data(data: &dataview::File, charts: &dataview::File) -> Result<(), IpcError> {
+    for (key, values) in &data.data {
+        if !charts.chart.contains_key(key) {
+            return Err(IpcError::UnknownChart { key: key.clone() });
+        }
+        if charts.dataview.r#type == dataview::Type::XY && !values.len().is_multiple_of(2) {
+            return Err(IpcError::OddLength {
+                key: key.clone(),
+                len: values.len(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// A binary data frame: the values to append to a series.
+///
+/// On the wire, a frame is made of:
+/// - the size of the rest of the frame (u32, little-endian)
+/// - the size of the series id (u8)
+/// - the series id (UTF-8)
+/// - the values (f64, little-endian), as x, y pairs for XY charts
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    pub key: String,
+    pub values: Vec<f64>,
+}
+
+impl Frame {
+    /// Decode a frame from its payload (without the size prefix)
+    pub fn decode(payload: &[u8]) -> Result<Self, String> {
+        let (size, payload) = match payload.split_first() {
+            Some((size, payload)) => (*size as usize, payload),
+            None => {
+                return Err("Empty frame".to_string());
+            }
+        };
+        if payload.len() < size {
+            return Err(format!("Series id of {} bytes exceeds the frame", size));
+        }
+        let (key, values) = payload.split_at(size);
+        let key = std::str::from_utf8(key).map_err(|e| format!("Invalid series id: {}", e))?;
+        if !values.len().is_multiple_of(8) {
+            return Err(format!(
+                "Values of {} bytes are not a list of f64",
+                values.len()
+            ));
+        }
+        let values = values
+            .chunks_exact(8)
+            .map(|value| f64::from_le_bytes(value.try_into().unwrap()))
+            .collect();
+        Ok(Self {
+            key: key.to_string(),
+            values,
+        })
+    }
+}
+
+/// Find the end of the JSON messages of a stream, byte after byte:
+/// a message ends with the closing brace of its object, so it may span
+/// several lines (e.g. a pretty printed file). Out of an object, a new
+/// line ends the message, to report a line which is not an object.
+#[derive(Debug, Default)]
+pub struct JsonScanner {
+    // Nesting depth of the objects and arrays
+    depth: usize,
+    // In a string, and after a backslash in this string
+    string: bool,
+    escape: bool,
+}
+
+impl JsonScanner {
+    /// Scan the next byte of a message: return true if it ends the message
+    pub fn scan(&mut self, c: u8) -> bool {
+        if self.string {
+            match (self.escape, c) {
+                (true, _) => self.escape = false,
+                (false, b'\\') => self.escape = true,
+                (false, b'"') => self.string = false,
+                _ => {}
+            }
+            return false;
+        }
+        match c {
+            b'"' => self.string = true,
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' => {
+                self.depth = self.depth.saturating_sub(1);
+                return self.depth == 0;
+            }
+            b'\n' => return self.depth == 0,
+            _ => {}
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(e: &IpcError) -> (Option<usize>, Option<usize>) {
+        match e {
+            IpcError::Parse { line, column, .. } => (*line, *column),
+            _ => panic!("not a parse error: {}", e),
+        }
+    }
+
+    #[test]
+    fn toml_position() {
+        let buff = "[dataview]\ntitle = 'é' x\n";
+        let e = toml::from_str::<dataview::File>(buff).unwrap_err();
+        let e = IpcError::parse(buff, e);
+        // Columns count characters, not bytes
+        assert_eq!(position(&e), (Some(2), Some(13)));
+        assert_eq!(e.kind(), "parse");
+        assert!(e
+            .to_string()
+            .starts_with("Parse error at line 2 column 13: "));
+
+        let buff = "[dataview]\ntype = 'pie'\n";
+        let e = toml::from_str::<dataview::File>(buff).unwrap_err();
+        let e = IpcError::parse(buff, e);
+        assert_eq!(position(&e), (Some(2), Some(8)));
+    }
+
+    #[test]
+    fn json_position() {
+        let buff = "{\"dataview\": {\n  \"title\": }}";
+        let e = serde_json::from_str::<dataview::File>(buff).unwrap_err();
+        let e = IpcError::json(e);
+        assert_eq!(position(&e), (Some(2), Some(12)));
+        // The position is not repeated in the message
+        assert_eq!(
+            e.to_string(),
+            "Parse error at line 2 column 12: expected value"
+        );
+    }
+
+    // Payload of a frame, without its size prefix
+    fn payload(key: &[u8], values: &[f64]) -> Vec<u8> {
+        let mut payload = vec![key.len() as u8];
+        payload.extend_from_slice(key);
+        for value in values {
+            payload.extend_from_slice(&value.to_le_bytes());
+        }
+        payload
+    }
+
+    #[test]
+    fn decode_frame() {
+        let frame = Frame {
+            key: "série".to_string(),
+            values: vec![1.0, -2.5, f64::INFINITY],
+        };
+        let decoded = Frame::decode(&payload(frame.key.as_bytes(), &frame.values));
+        assert_eq!(decoded, Ok(frame));
+
+        let decoded = Frame::decode(&payload(b"a", &[])).unwrap();
+        assert_eq!(decoded.key, "a");
+        assert!(decoded.values.is_empty());
+    }
+
+    #[test]
+    fn decode_invalid_frame() {
+        assert_eq!(Frame::decode(&[]), Err("Empty frame".to_string()));
+
+        let mut frame = payload(b"abc", &[]);
+        frame[0] = 4;
+        assert_eq!(
+            Frame::decode(&frame),
+            Err("Series id of 4 bytes exceeds the frame".to_string())
+        );
+
+        let frame = payload(&[0x61, 0xff], &[1.0]);
+        let e = Frame::decode(&frame).unwrap_err();
+        assert!(e.starts_with("Invalid series id: "), "{}", e);
+
+        let mut frame = payload(b"a", &[1.0]);
+        frame.pop();
+        assert_eq!(
+            Frame::decode(&frame),
+            Err("Values of 7 bytes are not a list of f64".to_string())
+        );
+    }
+
+    // Split a stream into its JSON messages
+    fn split_json(stream: &str) -> Vec<&str> {
+        let mut messages = vec![];
+        let mut scanner = JsonScanner::default();
+        let mut start = 0;
+        for (i, c) in stream.bytes().enumerate() {
+            if scanner.scan(c) {
+                messages.push(&stream[start..=i]);
+                scanner = JsonScanner::default();
+                start = i + 1;
+            }
+        }
+        messages.push(&stream[start..]);
+        messages
+    }
+
+    #[test]
+    fn json_messages() {
+        // One message per line
+        let stream = "{\"data\": {\"1\": [1, 2]}}\n{\"data\": {}}\n";
+        assert_eq!(
+            split_json(stream),
+            vec![
+                "{\"data\": {\"1\": [1, 2]}}",
+                "\n",
+                "{\"data\": {}}",
+                "\n",
+                ""
+            ]
+        );
+
+        // Pretty printed, with braces and escaped quotes in the strings
+        let stream = "{\n  \"title\": \"} \\\" {\"\n}{}";
+        assert_eq!(
+            split_json(stream),
+            vec!["{\n  \"title\": \"} \\\" {\"\n}", "{}", ""]
+        );
+
+        // Not an object
+        assert_eq!(split_json("[1]\nabc\n{"), vec!["[1]", "\n", "abc\n", "{"]);
+    }
+
+    #[test]
+    fn error_reply() {
+        let e = IpcError::OddLength {
+            key: "a".to_string(),
+            len: 3,
+        };
+        let reply = Reply::error(&e).to_bytes(dataview::Encoding::Json);
+        let reply: serde_json::Value = serde_json::from_slice(&reply[..reply.len() - 1]).unwrap();
+        assert_eq!(reply["status"], "error");
+        assert_eq!(reply["error"], "odd_length");
+        assert_eq!(reply["key"], "a");
+        assert!(reply.get("line").is_none());
+    }
+}
@@ -0,0 +1,20 @@
+//! Data Viewer library: the dataview file format and a client
+//! streaming data to a running viewer.
+
+pub mod client;
+pub mod dataview;
+
+// Internals of the viewer which do not depend on GTK: they are shared
+// with the viewer binary but are not part of the library API.
+#[doc(hidden)]
+pub mod csv;
+#[doc(hidden)]
+pub mod histogram;
+#[doc(hidden)]
+pub mod ipc;
+#[doc(hidden)]
+pub mod lod;
+#[doc(hidden)]
+pub mod ticks;
+#[doc(hidden)]
+pub mod utils;
@@ -0,0 +1,476 @@
+//! Level of detail pyramid and spatial index of the series drawn by
+//! the XY and Line charts.
+
+use crate::dataview;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Random access to the (x, y) points of a series
+#[derive(Clone, Copy)]
+pub enum Points<'a> {
+    /// A list of x, y pairs
+    XY(&'a [f64]),
+    /// A list of y values, with x = start + index * step
+    Line(&'a [f64], f64, f64),
+}
+
+impl<'a> Points<'a> {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::XY(data) => data.len() / 2,
+            Self::Line(data, _, _) => data.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> (f64, f64) {
+        match self {
+            Self::XY(data) => (data[2 * index], data[2 * index + 1]),
+            Self::Line(data, start, step) => (start + (index as f64) * step, data[index]),
+        }
+    }
+}
+
+// Number of buckets (or points) summarized by a bucket of the next level
+const BRANCHING: usize = 4;
+
+// A point of a series, with its index in the series
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub index: usize,
+    pub x: f64,
+    pub y: f64,
+}
+
+// Summary of consecutive points of a series: their first, last, lowest
+// and highest points. When the points fit in a pixel column, drawing
+// these four points in order gives the same pixels than drawing all of them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Bucket {
+    first: Point,
+    last: Point,
+    min: Point,
+    max: Point,
+}
+
+impl Bucket {
+    fn new(point: Point) -> Self {
+        Self {
+            first: point,
+            last: point,
+            min: point,
+            max: point,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.last.index - self.first.index + 1
+    }
+
+    // Append the following points to this bucket
+    fn merge(&mut self, other: &Self) {
+        self.last = other.last;
+        if other.min.y < self.min.y {
+            self.min = other.min;
+        }
+        if other.max.y > self.max.y {
+            self.max = other.max;
+        }
+    }
+
+    // The points to draw, in the series order
+    fn points(&self) -> impl Iterator<Item = Point> {
+        let mut points = [self.first, self.min, self.max, self.last];
+        points.sort_by_key(|point| point.index);
+        let mut previous = None;
+        points.into_iter().filter(move |point| {
+            let new = previous != Some(point.index);
+            previous = Some(point.index);
+            new
+        })
+    }
+}
+
+// Return the number of elements for which pred() is true,
+// pred() being true for the first elements and false for the next ones.
+fn partition<P: Fn(usize) -> bool>(len: usize, pred: P) -> usize {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match pred(mid) {
+            true => low = mid + 1,
+            false => high = mid,
+        }
+    }
+    low
+}
+
+// Level of detail pyramid and spatial index of a series, allowing to draw
+// a series of millions of points with one bucket per pixel column,
+// whatever the zoom, and to only touch the points visible in the view.
+// The pyramid summarizes fixed runs of points: the bucket of a pixel column
+// is merged from the largest runs it contains, so the x spacing of the points
+// does not matter. It is extended incrementally when points are appended.
+#[derive(Debug, PartialEq)]
+pub struct Lod {
+    // Number of points summarized
+    len: usize,
+    // The points are sorted by x: decimation is only possible in this case
+    sorted: bool,
+    // levels[0] buckets summarize BRANCHING points,
+    // levels[1] buckets summarize BRANCHING^2 points, ...
+    levels: Vec<Vec<Bucket>>,
+    // Indices of the points sorted by x, when the points are not sorted
+    order: Vec<usize>,
+}
+
+impl Default for Lod {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            sorted: true,
+            levels: vec![vec![]],
+            order: vec![],
+        }
+    }
+}
+
+impl Lod {
+    // Summarize the points appended to the series since the last update
+    pub fn update(&mut self, points: &Points) {
+        if points.len() < self.len {
+            *self = Self::default();
+        }
+        let (sorted, start) = (self.sorted, self.len);
+        for index in self.len..points.len() {
+            let (x, y) = points.get(index);
+            let point = Point { index, x, y };
+            // NaN are not sorted either, even as the first point
+            let before = self.levels[0].last().is_some_and(|last| x < last.last.x);
+            if before || x.is_nan() {
+                self.sorted = false;
+            }
+
+            let mut size = BRANCHING;
+            for level in self.levels.iter_mut() {
+                match level.last_mut() {
+                    Some(bucket) if bucket.len() < size => bucket.merge(&Bucket::new(point)),
+                    _ => level.push(Bucket::new(point)),
+                }
+                size *= BRANCHING;
+            }
+
+            // Add a level when the top level has too many buckets
+            let top = self.levels.last().unwrap();
+            if top.len() > BRANCHING {
+                let level = top
+                    .chunks(BRANCHING)
+                    .map(|chunk| {
+                        let mut bucket = chunk[0];
+                        for other in &chunk[1..] {
+                            bucket.merge(other);
+                        }
+                        bucket
+                    })
+                    .collect();
+                self.levels.push(level);
+            }
+        }
+        self.len = points.len();
+
+        // Index the points by x, merging the new points in the index
+        if !self.sorted {
+            let start = match sorted {
+                true => 0,
+                false => start,
+            };
+            let x = |index: &usize| points.get(*index).0;
+            let mut new: Vec<usize> = (start..self.len).collect();
+            new.sort_by(|a, b| x(a).total_cmp(&x(b)));
+            let old = std::mem::take(&mut self.order);
+            self.order = Vec::with_capacity(old.len() + new.len());
+            let (mut old, mut new) = (old.into_iter().peekable(), new.into_iter().peekable());
+            loop {
+                let index = match (old.peek(), new.peek()) {
+                    (Some(a), Some(b)) if x(a).total_cmp(&x(b)).is_le() => old.next(),
+                    (Some(_), Some(_)) => new.next(),
+                    (Some(_), None) => old.next(),
+                    (None, Some(_)) => new.next(),
+                    (None, None) => break,
+                };
+                self.order.push(index.unwrap());
+            }
+        }
+    }
+
+    // Range of the points between x_min and x_max, with one more point
+    // on each side to draw the lines going out of the view. The range is
+    // in the series for sorted series, or in the index otherwise.
+    fn range(&self, points: &Points, x_min: f64, x_max: f64) -> Range<usize> {
+        let x = |i: usize| match self.sorted {
+            true => points.get(i).0,
+            false => points.get(self.order[i]).0,
+        };
+        let start = partition(self.len, |i| x(i) < x_min);
+        let end = partition(self.len, |i| x(i) <= x_max);
+        start.saturating_sub(1)..(end + 1).min(self.len).max(start)
+    }
+
+    // Return the number of points between x_min and x_max
+    pub fn count(&self, points: &Points, x_min: f64, x_max: f64) -> usize {
+        self.range(points, x_min, x_max).len()
+    }
+
+    // Return true if the points between x_min and x_max are too many to be
+    // drawn on this number of pixel columns and can be decimated.
+    pub fn decimate(&self, count: usize, columns: f64) -> bool {
+        self.sorted && (count as f64) > columns
+    }
+
+    // Summarize the points from start to end (excluded), merging the
+    // largest buckets of the pyramid which fit in this range.
+    fn bucket(&self, points: &Points, start: usize, end: usize) -> Bucket {
+        let point = |index| {
+            let (x, y) = points.get(index);
+            Point { index, x, y }
+        };
+        let mut bucket = Bucket::new(point(start));
+        let mut index = start + 1;
+        while index < end {
+            let mut next = Bucket::new(point(index));
+            let mut size = 1;
+            for level in &self.levels {
+                let level_size = size * BRANCHING;
+                if !index.is_multiple_of(level_size) || index + level_size > end {
+                    break;
+                }
+                next = level[index / level_size];
+                size = level_size;
+            }
+            bucket.merge(&next);
+            index += size;
+        }
+        bucket
+    }
+
+    // The points to draw between x_min and x_max. When columns is given,
+    // the points are decimated to a bucket per pixel column, the view being
+    // this number of columns wide. Unsorted series return the lines going
+    // through the points.
+    pub fn points<'a>(
+        &'a self,
+        points: Points<'a>,
+        columns: Option<f64>,
+        x_min: f64,
+        x_max: f64,
+    ) -> Box<dyn Iterator<Item = Point> + 'a> {
+        let point = move |index| {
+            let (x, y) = points.get(index);
+            Point { index, x, y }
+        };
+        let width = columns.map(|columns| (x_max - x_min) / columns);
+        match (width, self.sorted) {
+            (Some(width), true) if width > 0.0 && width.is_finite() => {
+                let range = self.range(&points, x_min, x_max);
+                let mut buckets = vec![];
+                let mut index = range.start;
+                while index < range.end {
+                    let x = points.get(index).0;
+                    // The points out of the view are only drawn to reach it
+                    if x < x_min || x > x_max {
+                        buckets.push(Bucket::new(point(index)));
+                        index += 1;
+                        continue;
+                    }
+                    let column = ((x - x_min) / width).floor();
+                    let right = x_min + (column + 1.0) * width;
+                    let count = partition(range.end - index, |i| points.get(index + i).0 < right);
+                    let end = index + count.max(1);
+                    buckets.push(self.bucket(&points, index, end));
+                    index = end;
+                }
+                Box::new(buckets.into_iter().flat_map(|bucket| bucket.points()))
+            }
+            (_, true) => Box::new(self.range(&points, x_min, x_max).map(point)),
+            (_, false) => {
+                let mut indices = vec![];
+                for i in self.range(&points, x_min, x_max) {
+                    let index = self.order[i];
+                    if index > 0 {
+                        indices.push(index - 1);
+                    }
+                    indices.push(index);
+                    if index + 1 < self.len {
+                        indices.push(index + 1);
+                    }
+                }
+                indices.sort_unstable();
+                indices.dedup();
+                Box::new(indices.into_iter().map(point))
+            }
+        }
+    }
+}
+
+// Update the pyramid of each series of the file
+pub fn update<'a, F>(lods: &mut HashMap<String, Lod>, file: &'a dataview::File, points: F)
+where
+    F: Fn(&'a [f64]) -> Points<'a>,
+{
+    lods.retain(|key, _| file.data.contains_key(key));
+    for (key, data) in &file.data {
+        lods.entry(key.clone()).or_default().update(&points(data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pseudo-random y values, with x from the given function
+    fn series(len: usize, x: impl Fn(usize) -> f64) -> Vec<f64> {
+        let mut seed: u64 = 42;
+        let mut data = vec![];
+        for i in 0..len {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            data.push(x(i));
+            data.push((seed >> 33) as f64);
+        }
+        data
+    }
+
+    fn lod(data: &[f64]) -> Lod {
+        let mut lod = Lod::default();
+        lod.update(&Points::XY(data));
+        lod
+    }
+
+    fn indices<'a>(iter: impl Iterator<Item = Point> + 'a) -> Vec<usize> {
+        iter.map(|point| point.index).collect()
+    }
+
+    #[test]
+    fn incremental_update() {
+        let sorted = series(1000, |i| i as f64);
+        let unsorted = series(1000, |i| ((i * 7919) % 1000) as f64);
+        for data in [&sorted, &unsorted] {
+            let mut incremental = Lod::default();
+            for len in (0..1000).step_by(37).chain([1000]) {
+                incremental.update(&Points::XY(&data[..2 * len]));
+            }
+            assert_eq!(incremental, lod(data));
+        }
+    }
+
+    #[test]
+    fn shrunk_series() {
+        let data = series(100, |i| i as f64);
+        let mut shrunk = lod(&data);
+        let data = series(10, |i| (10 - i) as f64);
+        shrunk.update(&Points::XY(&data));
+        assert_eq!(shrunk, lod(&data));
+        assert!(!shrunk.sorted);
+    }
+
+    #[test]
+    fn bucket() {
+        let data = series(500, |i| i as f64);
+        let points = Points::XY(&data);
+        let lod = lod(&data);
+        for (start, end) in [(0, 500), (0, 1), (3, 4), (5, 300), (64, 128), (250, 499)] {
+            let mut expected = Bucket::new(Point {
+                index: start,
+                x: points.get(start).0,
+                y: points.get(start).1,
+            });
+            for index in start + 1..end {
+                let (x, y) = points.get(index);
+                expected.merge(&Bucket::new(Point { index, x, y }));
+            }
+            assert_eq!(lod.bucket(&points, start, end), expected);
+        }
+    }
+
+    #[test]
+    fn visible_range() {
+        let data = series(10, |i| i as f64);
+        let points = Points::XY(&data);
+        let lod = lod(&data);
+        assert_eq!(lod.range(&points, 2.5, 5.5), 2..7);
+        assert_eq!(lod.range(&points, 3.0, 5.0), 2..7);
+        assert_eq!(lod.range(&points, -5.0, 20.0), 0..10);
+        assert_eq!(lod.range(&points, -5.0, -1.0), 0..1);
+        assert_eq!(lod.range(&points, 20.0, 30.0), 9..10);
+        assert_eq!(lod.count(&points, 2.5, 5.5), 5);
+        assert_eq!(
+            indices(lod.points(points, None, 2.5, 5.5)),
+            vec![2, 3, 4, 5, 6]
+        );
+
+        let empty = Lod::default();
+        assert_eq!(empty.range(&Points::XY(&[]), 0.0, 1.0), 0..0);
+    }
+
+    #[test]
+    fn unsorted_and_nan() {
+        let data = [3.0, 0.0, 1.0, 0.0, f64::NAN, 0.0, 2.0, 0.0, 5.0, 0.0];
+        let points = Points::XY(&data);
+        let lod = lod(&data);
+        assert!(!lod.sorted);
+        assert_eq!(lod.order, vec![1, 3, 0, 4, 2]);
+        assert!(!lod.decimate(1000, 1.0));
+        // The lines going through the points in the view
+        assert_eq!(
+            indices(lod.points(points, Some(1.0), 1.5, 2.5)),
+            vec![0, 1, 2, 3, 4]
+        );
+        assert_eq!(
+            indices(lod.points(points, None, 4.5, 6.0)),
+            vec![0, 1, 2, 3, 4]
+        );
+
+        // NaN are not sorted
+        let lod = self::lod(&[0.0, 0.0, f64::NAN, 0.0]);
+        assert!(!lod.sorted);
+        let lod = self::lod(&[f64::NAN, 0.0, 1.0, 0.0]);
+        assert!(!lod.sorted);
+    }
+
+    #[test]
+    fn decimate_per_column() {
+        // A burst of points, then a few sparse points
+        let mut data = series(10000, |i| i as f64 / 10000.0);
+        data.extend(series(10, |i| 10.0 + i as f64));
+        let points = Points::XY(&data);
+        let lod = lod(&data);
+        let count = lod.count(&points, 0.0, 20.0);
+        assert!(lod.decimate(count, 20.0));
+
+        let decimated = indices(lod.points(points, Some(20.0), 0.0, 20.0));
+        // The burst fits in a column: its first, last, lowest and highest points
+        assert!(decimated.iter().filter(|index| **index < 10000).count() <= 4);
+        assert!(decimated.contains(&0) && decimated.contains(&9999));
+        // The sparse points are all drawn, in order
+        assert_eq!(
+            decimated.iter().filter(|index| **index >= 10000).count(),
+            10
+        );
+        assert!(decimated.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // The lowest and highest points of each column are kept
+        let (mut min, mut max) = (0, 0);
+        for index in 1..10000 {
+            let y = points.get(index).1;
+            if y < points.get(min).1 {
+                min = index;
+            }
+            if y > points.get(max).1 {
+                max = index;
+            }
+        }
+        assert!(decimated.contains(&min) && decimated.contains(&max));
+    }
+}
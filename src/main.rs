@@ -1,37 +1,79 @@
+use ::dataviewer::{csv, dataview, ipc, utils};
 use gtk::prelude::*;
 use gtk::{gio, glib};
 use gtk4 as gtk;
-use std::path::PathBuf;
 use ui::*;
 
+mod args;
 mod canvas;
 mod chart;
-mod dataview;
 mod dataviewer;
+mod exec;
+mod export;
 mod server;
 mod stream;
 mod ui;
-mod utils;
 
 fn main() -> glib::ExitCode {
-    // Open ipc listening socket if it's not already running
-    let ipc = match server::ipc_running() {
-        false => Some(server::ipc_listen_socket()),
-        true => None,
+    // Export subcommand is headless: it must not open any window nor socket
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(|arg| arg.as_str()) == Some("export") {
+        return match export::main(&args[2..]) {
+            Ok(()) => glib::ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                glib::ExitCode::FAILURE
+            }
+        };
+    }
+
+    let args = match args::Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return glib::ExitCode::FAILURE;
+        }
+    };
+
+    // Open ipc listening sockets if they are not already running
+    let custom_socket = !args.sockets.is_empty() || std::env::var_os(server::SOCKET_ENV).is_some();
+    let sockets = match args.sockets.is_empty() {
+        true => vec![server::default_socket()],
+        false => args.sockets.clone(),
     };
+    let mut endpoints = vec![];
+    for path in &sockets {
+        if server::ipc_running(path) {
+            continue;
+        }
+        match server::ipc_listen_socket(path) {
+            Ok(endpoint) => endpoints.push(endpoint),
+            Err(e) => println!("Failed to listen on {:?}: {:?}", path, e),
+        }
+    }
+
+    // Open the TCP listening sockets for remote clients
+    for address in &args.listen {
+        match server::tcp_listen_socket(address) {
+            Ok(endpoint) => endpoints.push(endpoint),
+            Err(e) => println!("Failed to listen on {}: {:?}", address, e),
+        }
+    }
 
     // deamonize the application if streaming is required
-    for arg in std::env::args() {
-        if arg == "--stream" {
-            let daemon = daemonize::Daemonize::new();
-            if let Err(e) = daemon.start() {
-                println!("Failed to daemonize: {:?}", e);
-            }
+    if args.stream {
+        let daemon = daemonize::Daemonize::new();
+        if let Err(e) = daemon.start() {
+            println!("Failed to daemonize: {:?}", e);
         }
     }
 
     let mut flags = gio::ApplicationFlags::empty();
     flags.insert(gio::ApplicationFlags::HANDLES_COMMAND_LINE);
+    if custom_socket || !args.listen.is_empty() {
+        // A viewer using its own sockets runs independently of the others
+        flags.insert(gio::ApplicationFlags::NON_UNIQUE);
+    }
     let app = gtk::Application::builder()
         .application_id("org.gtk.dataviewer")
         .flags(flags)
@@ -45,24 +87,71 @@ fn main() -> glib::ExitCode {
                 return 1;
             }
         };
-        for arg in cmdline.arguments().iter().skip(1) {
-            if arg.to_str() == Some("--stream") {
-                continue;
+        let arguments = cmdline.arguments();
+        let arguments = arguments
+            .iter()
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().to_string());
+        let args = match args::Args::parse(arguments) {
+            Ok(args) => args,
+            Err(e) => {
+                window.error(e.wrap_err("Invalid arguments"));
+                return 1;
+            }
+        };
+        if args.stdin {
+            match cmdline.stdin() {
+                Some(stdin) => {
+                    let stream = stream::Stream::new(stdin, None);
+                    server::run_stream(app, &window, stream, "stdin");
+                }
+                None => window.error_str("Standard input is not available"),
             }
-            let path = PathBuf::from(arg);
+        }
+        for path in &args.follow {
             let path = match path.is_absolute() {
-                true => path,
+                true => path.clone(),
                 false => cwd.join(path),
             };
-            if let Err(e) = window.new_draw_area_from_file(&path) {
-                window.error(e.wrap_err(format!("Failed to open {:?}", &path)));
+            match stream::Stream::follow(&path) {
+                Ok(stream) => {
+                    let label = path.file_name().unwrap_or_default().to_string_lossy();
+                    server::run_stream(app, &window, stream, &label);
+                }
+                Err(e) => {
+                    let e = eyre::eyre!(e);
+                    window.error(e.wrap_err(format!("Failed to follow {:?}", &path)));
+                }
+            }
+        }
+        for command in &args.exec {
+            if let Err(e) = exec::run(app, &window, command, &cwd) {
+                window.error(e.wrap_err(format!("Failed to run {}", command)));
+            }
+        }
+        for path in &args.files {
+            let path = match path.is_absolute() {
+                true => path.clone(),
+                false => cwd.join(path),
+            };
+            let draw_area = match window.new_draw_area_from_file(&path, &args.csv) {
+                Ok(draw_area) => draw_area,
+                Err(e) => {
+                    window.error(e.wrap_err(format!("Failed to open {:?}", &path)));
+                    continue;
+                }
+            };
+            if args.auto_reload {
+                if let Err(e) = window.set_auto_reload(&draw_area, true) {
+                    window.error(e.wrap_err(format!("Failed to watch {:?}", &path)));
+                }
             }
         }
         0
     });
     app.connect_startup(move |app| {
-        if let Some(ipc) = ipc.clone() {
-            server::run(app, ipc);
+        for endpoint in &endpoints {
+            server::run(app, endpoint.clone());
         }
     });
 
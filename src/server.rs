@@ -1,91 +1,341 @@
 use crate::*;
+use eyre::{eyre, Result};
 use gtk::{gio, glib};
 use gtk4 as gtk;
-use std::path::PathBuf;
+use ipc::{check_data, IpcError, Reply};
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
 
-fn server_handle_update(window: &gtk::Window, update: dataview::File) {
-    let notebook = window.get_notebook();
-    let page = match notebook.pages().item(0) {
-        Some(page) => page.downcast::<gtk::NotebookPage>().unwrap(),
-        None => {
-            return;
-        }
-    };
-    let mut draw_area = page.child().downcast::<gtk::DrawingArea>().unwrap();
-    let context = draw_area.get_mut_context();
-    context.dataviewer.update(update);
+/// State of an ipc client connection
+struct Client {
+    app: gtk::Application,
+    window: Option<gtk::Window>,
+    /// Drawing area created by this client and receiving its updates
+    draw_area: Option<gtk::DrawingArea>,
+    /// Acknowledge each message
+    ack: bool,
+    /// Encoding of the messages and replies
+    encoding: dataview::Encoding,
+    /// Label of the tabs created by this client
+    label: String,
+    /// Replace the content of draw_area with the next declaration,
+    /// instead of opening a new tab.
+    reload: bool,
 }
 
-fn server_handle_message(window: &gtk::Window, file: dataview::File) {
-    println!("message = {:?}", file);
+impl Client {
+    fn new(app: &gtk::Application, label: &str) -> Self {
+        Self {
+            app: app.clone(),
+            window: None,
+            draw_area: None,
+            ack: false,
+            encoding: dataview::Encoding::Toml,
+            label: label.to_string(),
+            reload: false,
+        }
+    }
+
+    /// Get the window used by this client, opening one if required
+    fn window(&mut self) -> gtk::Window {
+        if self.window.is_none() {
+            println!("Opening new window using ipc socket");
+            self.window = match self.app.find_empty_window() {
+                Some(window) => Some(window),
+                None => Some(self.app.new_window()),
+            };
+        }
+        self.window.clone().unwrap()
+    }
+
+    fn handle_update(
+        &self,
+        draw_area: &gtk::DrawingArea,
+        update: dataview::File,
+    ) -> Result<(), IpcError> {
+        let mut draw_area = draw_area.clone();
+        let context = draw_area.get_mut_context();
+        check_data(&update, context.dataviewer.file())?;
+        context.dataviewer.update(update);
+        Ok(())
+    }
+
+    fn handle_message(&mut self, mut file: dataview::File) -> Result<(), IpcError> {
+        println!("message = {:?}", file);
+
+        if let Some(ipc) = file.ipc.take() {
+            self.ack = ipc.ack;
+            if let Some(encoding) = ipc.encoding {
+                self.encoding = encoding;
+            }
+        }
+
+        // A message targeting a named stream is sent to the drawing area
+        // created for this stream, which may be fed by several clients.
+        let stream = file.stream.take();
+        if let Some(stream) = &stream {
+            if let Some(draw_area) = self.app.find_stream(stream) {
+                self.draw_area = Some(draw_area);
+                if !file.data.is_empty() {
+                    self.handle_update(self.draw_area.as_ref().unwrap(), file)?;
+                }
+                return Ok(());
+            }
+        }
+
+        if !file.chart.is_empty() && self.reload && self.draw_area.is_some() {
+            check_data(&file, &file)?;
+            let mut draw_area = self.draw_area.clone().unwrap();
+            let context = draw_area.get_mut_context();
+            context.dataviewer.reload(file).map_err(IpcError::Invalid)?;
+            context.stream = stream;
+            self.reload = false;
+        } else if !file.chart.is_empty() {
+            check_data(&file, &file)?;
+            let window = self.window();
+            let mut draw_area = window
+                .new_draw_area(file, &self.label)
+                .map_err(IpcError::Invalid)?;
+            draw_area.get_mut_context().stream = stream;
+            self.draw_area = Some(draw_area);
+        } else if !file.data.is_empty() {
+            match &self.draw_area {
+                Some(draw_area) => self.handle_update(draw_area, file)?,
+                None => {
+                    return Err(IpcError::NoChart);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse and handle a message received from the client
+    /// and return the reply to send back, if any.
+    fn handle_buffer(&mut self, buff: &str) -> Option<Reply> {
+        let message = match self.encoding {
+            dataview::Encoding::Json => {
+                serde_json::from_str::<dataview::File>(buff).map_err(IpcError::json)
+            }
+            _ => toml::from_str::<dataview::File>(buff).map_err(|e| IpcError::parse(buff, e)),
+        };
+        let result = message.and_then(|message| self.handle_message(message));
+        self.reply(result)
+    }
 
-    if !file.chart.is_empty() {
-        let _ = window.new_draw_area(file, "ipc://tmp/dataviewer.ipc");
-    } else if !file.data.is_empty() {
-        server_handle_update(window, file);
+    /// Decode and handle a binary frame received from the client
+    /// and return the reply to send back, if any.
+    fn handle_frame(&mut self, payload: &[u8]) -> Option<Reply> {
+        let result = ipc::Frame::decode(payload)
+            .map_err(|message| IpcError::Parse {
+                message: format!("Invalid binary frame: {}", message),
+                line: None,
+                column: None,
+            })
+            .and_then(|frame| {
+                let mut update = dataview::File::default();
+                update.data.insert(frame.key, frame.values);
+                match &self.draw_area {
+                    Some(draw_area) => self.handle_update(draw_area, update),
+                    None => Err(IpcError::NoChart),
+                }
+            });
+        self.reply(result)
+    }
+
+    /// Get the reply to a message from the result of its handling
+    fn reply(&mut self, result: Result<(), IpcError>) -> Option<Reply> {
+        match result {
+            Ok(()) => match self.ack {
+                true => Some(Reply::ack()),
+                false => None,
+            },
+            Err(e) => {
+                // Report the error to the user without interrupting the application
+                println!("IPC error: {}", e);
+                self.window().error_bar(&e.to_string());
+                Some(Reply::error(&e))
+            }
+        }
     }
 }
 
-pub fn ipc_running() -> bool {
-    let ipc_name = "/tmp/dataviewer.ipc";
+pub use ::dataviewer::client::{default_socket, SOCKET_ENV};
 
-    std::os::unix::net::UnixStream::connect(ipc_name).is_ok()
+/// A listening socket accepting ipc clients
+#[derive(Clone)]
+pub struct Endpoint {
+    socket: gio::Socket,
+    /// Label of the tabs created by the clients of this endpoint
+    label: String,
 }
 
-pub fn ipc_listen_socket() -> gio::Socket {
-    let ipc_name = "/tmp/dataviewer.ipc";
-    let path = PathBuf::from(ipc_name);
-    let _ = std::fs::remove_file(&path);
-    let address = gio::UnixSocketAddress::new(&PathBuf::from(&path));
-    let server = gio::Socket::new(
+pub fn ipc_running(path: &Path) -> bool {
+    std::os::unix::net::UnixStream::connect(path).is_ok()
+}
+
+pub fn ipc_listen_socket(path: &Path) -> Result<Endpoint> {
+    // Only remove a stale socket: never a regular file
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(eyre!("{:?} exists and is not a socket", path));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let address = gio::UnixSocketAddress::new(path);
+    let socket = gio::Socket::new(
         gio::SocketFamily::Unix,
         gio::SocketType::Stream,
         gio::SocketProtocol::Default,
-    )
-    .unwrap();
-    server.bind(&address, true).unwrap();
-    server.listen().unwrap();
-    println!("Listening on ipc://{}", ipc_name);
-    server
+    )?;
+    socket.bind(&address, true)?;
+    socket.listen()?;
+    let label = format!("ipc://{}", path.display());
+    println!("Listening on {}", label);
+    Ok(Endpoint { socket, label })
+}
+
+/// Listen on a TCP address, given as ADDRESS:PORT
+pub fn tcp_listen_socket(address: &str) -> Result<Endpoint> {
+    let address: std::net::SocketAddr = address
+        .parse()
+        .map_err(|e| eyre!("Invalid address '{}': {}", address, e))?;
+    let family = match address.is_ipv4() {
+        true => gio::SocketFamily::Ipv4,
+        false => gio::SocketFamily::Ipv6,
+    };
+    let socket = gio::Socket::new(family, gio::SocketType::Stream, gio::SocketProtocol::Tcp)?;
+    socket.bind(&gio::InetSocketAddress::from(address), true)?;
+    socket.listen()?;
+    let label = format!("tcp://{}", address);
+    println!("Listening on {}", label);
+    Ok(Endpoint { socket, label })
+}
+
+/// A message read from a client, depending on the encoding
+enum Message {
+    Text(String),
+    Frame(Vec<u8>),
 }
 
-pub fn run(app: &gtk::Application, server: gio::Socket) {
+/// Read and handle the messages of a client until the end of its stream
+async fn serve(mut client: Client, mut stream: stream::Stream) {
+    // A client starting with a JSON object speaks JSON
+    match stream.peek().await {
+        Ok(Some(b'{')) => client.encoding = dataview::Encoding::Json,
+        Ok(_) => {}
+        Err(e) => {
+            println!("IPC Connection error: {:?}", e);
+            return;
+        }
+    }
+
+    loop {
+        // The reply uses the encoding of the message it answers
+        let encoding = client.encoding;
+        let reopens = stream.reopens();
+        let message = match encoding {
+            dataview::Encoding::Toml => stream
+                .read_message(false)
+                .await
+                .map(|m| m.map(Message::Text)),
+            dataview::Encoding::Json => stream
+                .read_message(true)
+                .await
+                .map(|m| m.map(Message::Text)),
+            dataview::Encoding::Binary => stream.read_frame().await.map(|m| m.map(Message::Frame)),
+        };
+        let message = match message {
+            Ok(Some(message)) => message,
+            Ok(None) => {
+                println!("IPC Connection closed");
+                break;
+            }
+            Err(e) => {
+                println!("IPC Connection error: {:?}", e);
+                break;
+            }
+        };
+
+        // The new content of a followed file replaces the content of its tab
+        if stream.reopens() != reopens {
+            client.reload = true;
+        }
+
+        let reply = match message {
+            Message::Text(buff) => {
+                if encoding == dataview::Encoding::Json && buff.trim().is_empty() {
+                    continue;
+                }
+                client.handle_buffer(&buff)
+            }
+            Message::Frame(payload) => client.handle_frame(&payload),
+        };
+        if let Some(reply) = reply {
+            if let Err(e) = stream.write_all(reply.to_bytes(encoding)).await {
+                println!("IPC Connection error: {:?}", e);
+                break;
+            }
+        }
+    }
+}
+
+/// Read the messages of a stream which is not an ipc client
+/// (e.g. stdin or a followed file) and show them in this window.
+pub fn run_stream(
+    app: &gtk::Application,
+    window: &gtk::Window,
+    stream: stream::Stream,
+    label: &str,
+) {
+    let mut client = Client::new(app, label);
+    client.window = Some(window.clone());
+    let main_context = glib::MainContext::default();
+    main_context.spawn_local(serve(client, stream));
+}
+
+/// Read the messages of a stream into an existing drawing area, until
+/// the end of the stream: the first declaration replaces its content.
+pub async fn serve_draw_area(
+    app: &gtk::Application,
+    window: &gtk::Window,
+    draw_area: &gtk::DrawingArea,
+    stream: stream::Stream,
+    label: &str,
+) {
+    let mut client = Client::new(app, label);
+    client.window = Some(window.clone());
+    client.draw_area = Some(draw_area.clone());
+    client.reload = true;
+    serve(client, stream).await;
+}
+
+pub fn run(app: &gtk::Application, endpoint: Endpoint) {
     let main_context = glib::MainContext::default();
     let app = app.clone();
     main_context.spawn_local(async move {
         let listener = gio::SocketListener::new();
         listener
-            .add_socket(&server, None as Option<&glib::Object>)
+            .add_socket(&endpoint.socket, None as Option<&glib::Object>)
             .unwrap();
 
         loop {
             let app = app.clone();
-            let (client, _) = listener.accept_future().await.unwrap();
+            let label = endpoint.label.clone();
+            let client = match listener.accept_future().await {
+                Ok((client, _)) => client,
+                Err(e) => {
+                    println!("Failed to accept ipc client: {:?}", e);
+                    continue;
+                }
+            };
             println!("New ipc client connected");
 
             // Read dataview::File from ipc socket
             let main_context = glib::MainContext::default();
             main_context.spawn_local(async move {
-                let mut stream = stream::Stream::new(&client);
-                let mut window = None;
-                loop {
-                    let buff = stream.read_utf8_upto(0).await;
-                    if buff.is_empty() {
-                        println!("IPC Connection closed");
-                        break;
-                    }
-                    if window.is_none() {
-                        println!("Opening new window using ipc socket");
-                        window = match app.find_empty_window() {
-                            Some(window) => Some(window),
-                            None => Some(app.new_window()),
-                        };
-                    }
-                    let window = window.as_ref().unwrap();
-
-                    let message: dataview::File = toml::from_str(&buff).unwrap();
-                    server_handle_message(window, message);
-                }
+                let stream = stream::Stream::from_connection(&client);
+                let client = Client::new(&app, &label);
+                serve(client, stream).await;
             });
         }
     });
@@ -1,45 +1,273 @@
+use crate::ipc;
 use gtk::prelude::*;
 use gtk::{gio, glib};
 use gtk4 as gtk;
 use std::collections::VecDeque;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Interval between two reads when following a file
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Maximum size of a binary frame, to detect a client out of sync
+const FRAME_MAX_SIZE: usize = 64 * 1024 * 1024;
+
+/// Maximum size of a text message, to drop a client never ending its message
+const MESSAGE_MAX_SIZE: usize = 64 * 1024 * 1024;
+
+/// A file followed by a stream
+struct Follow {
+    path: PathBuf,
+    /// Inode of the opened file, to detect its replacement
+    inode: u64,
+    /// Number of bytes read, to detect its truncation
+    offset: u64,
+    /// A message ending with its delimiter was read: the file is a stream
+    /// of messages, instead of a plain dataview file which is read whole.
+    delimited: bool,
+}
 
 pub struct Stream {
     buffer: std::collections::VecDeque<u8>,
     input: gio::InputStream,
+    output: Option<gio::OutputStream>,
+    /// Wait for more data at the end of the file (like tail -f)
+    follow: Option<Follow>,
+    /// Number of times the followed file was reopened
+    reopens: usize,
 }
 
 impl Stream {
-    pub fn new(iostream: &gio::SocketConnection) -> Self {
+    pub fn new(input: gio::InputStream, output: Option<gio::OutputStream>) -> Self {
         Self {
             buffer: VecDeque::new(),
-            input: iostream.input_stream(),
+            input,
+            output,
+            follow: None,
+            reopens: 0,
         }
     }
 
-    pub async fn read_utf8_upto(&mut self, upto: u8) -> String {
-        let mut string = String::new();
-        loop {
-            while let Some(c) = self.buffer.pop_front() {
-                if c == upto {
-                    return string;
-                }
-                if c.is_ascii() {
-                    string.push(c as char);
-                }
+    fn open(path: &Path) -> Result<(gio::InputStream, Follow), glib::Error> {
+        let file = gio::File::for_path(path);
+        let input = file.read(None::<&gio::Cancellable>)?;
+        let follow = Follow {
+            path: path.to_path_buf(),
+            inode: std::fs::metadata(path).map(|m| m.ino()).unwrap_or(0),
+            offset: 0,
+            delimited: false,
+        };
+        Ok((input.upcast(), follow))
+    }
+
+    /// Open a file which is still being written: reaching its end
+    /// does not close the stream, more data is waited for.
+    /// Messages appended to the file must end with their delimiter (as on
+    /// a socket), the end of a message is never guessed from write timings.
+    /// A file without any delimited message is a plain dataview file:
+    /// its content is a single message, read again whenever the file grows.
+    /// The file is also read again from its start if it is truncated or replaced.
+    pub fn follow(path: &Path) -> Result<Self, glib::Error> {
+        let (input, follow) = Self::open(path)?;
+        let mut stream = Self::new(input, None);
+        stream.follow = Some(follow);
+        Ok(stream)
+    }
+
+    /// Number of times the followed file was reopened: the messages read
+    /// after a reopening come from the new content of the file.
+    pub fn reopens(&self) -> usize {
+        self.reopens
+    }
+
+    /// Reopen the followed file if it was truncated or replaced,
+    /// or if it is a plain dataview file which grew,
+    /// dropping the data read from the previous file.
+    fn reopen(&mut self) -> Result<bool, glib::Error> {
+        let follow = match &self.follow {
+            Some(follow) => follow,
+            None => {
+                return Ok(false);
+            }
+        };
+        // A removed file may be created again: keep waiting for it
+        let metadata = match std::fs::metadata(&follow.path) {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                return Ok(false);
             }
+        };
+        let replaced = metadata.ino() != follow.inode || metadata.len() < follow.offset;
+        let grown = !follow.delimited && follow.offset > 0 && metadata.len() > follow.offset;
+        if !replaced && !grown {
+            return Ok(false);
+        }
+        if replaced {
+            println!("{:?} was truncated or replaced: reopening it", follow.path);
+        }
+        let (input, follow) = Self::open(&follow.path)?;
+        self.input = input;
+        self.follow = Some(follow);
+        self.buffer.clear();
+        self.reopens += 1;
+        Ok(true)
+    }
+
+    pub fn from_connection(iostream: &gio::SocketConnection) -> Self {
+        Self::new(iostream.input_stream(), Some(iostream.output_stream()))
+    }
 
+    /// Record that a followed file is a stream of delimited messages
+    fn delimited(&mut self) {
+        if let Some(follow) = &mut self.follow {
+            follow.delimited = true;
+        }
+    }
+
+    /// Read more data from the input stream into the buffer.
+    /// Return false at the end of the stream. A followed file has no end,
+    /// except for the pending data of a plain dataview file.
+    async fn fill(&mut self, mut pending: bool) -> Result<bool, glib::Error> {
+        loop {
             let buffer = vec![0; 4096];
             let (mut buffer, size) = self
                 .input
                 .read_future(buffer, glib::source::Priority::DEFAULT)
                 .await
-                .unwrap();
+                .map_err(|(_, e)| e)?;
 
             if size == 0 {
-                return string;
+                let delimited = match &self.follow {
+                    Some(follow) => follow.delimited,
+                    None => {
+                        return Ok(false);
+                    }
+                };
+                if self.reopen()? {
+                    pending = false;
+                    continue;
+                }
+                if pending && !delimited {
+                    return Ok(false);
+                }
+                glib::timeout_future(FOLLOW_INTERVAL).await;
+                continue;
+            }
+            if let Some(follow) = &mut self.follow {
+                follow.offset += size as u64;
             }
             buffer.truncate(size);
             self.buffer.extend(buffer);
+            return Ok(true);
+        }
+    }
+
+    /// Return the next byte of the stream without consuming it,
+    /// or None at the end of the stream.
+    pub async fn peek(&mut self) -> Result<Option<u8>, glib::Error> {
+        if self.buffer.is_empty() && !self.fill(false).await? {
+            return Ok(None);
+        }
+        Ok(self.buffer.front().copied())
+    }
+
+    /// Read a text message, or None at the end of the stream:
+    /// a TOML message ends with a NUL (excluded), a JSON message
+    /// ends with its closing brace (see ipc::JsonScanner).
+    /// Fail if the message exceeds MESSAGE_MAX_SIZE bytes.
+    pub async fn read_message(&mut self, json: bool) -> Result<Option<String>, glib::Error> {
+        let mut string = String::new();
+        let mut size = 0;
+        let mut scanner = ipc::JsonScanner::default();
+        let mut reopens = self.reopens;
+        loop {
+            while let Some(c) = self.buffer.pop_front() {
+                let end = match json {
+                    true => scanner.scan(c),
+                    false => c == 0,
+                };
+                if c != 0 && c.is_ascii() {
+                    string.push(c as char);
+                }
+                if end {
+                    self.delimited();
+                    return Ok(Some(string));
+                }
+                size += 1;
+            }
+
+            if size > MESSAGE_MAX_SIZE {
+                return Err(glib::Error::new(
+                    gio::IOErrorEnum::InvalidData,
+                    &format!("Message exceeds {} bytes", MESSAGE_MAX_SIZE),
+                ));
+            }
+
+            if !self.fill(size > 0).await? {
+                return match string.is_empty() {
+                    true => Ok(None),
+                    false => Ok(Some(string)),
+                };
+            }
+
+            // Drop the message started in the previous followed file
+            if self.reopens != reopens {
+                reopens = self.reopens;
+                string.clear();
+                size = 0;
+                scanner = ipc::JsonScanner::default();
+            }
+        }
+    }
+
+    /// Read exactly size bytes, or None at the end of the stream
+    async fn read_exact(&mut self, size: usize) -> Result<Option<Vec<u8>>, glib::Error> {
+        while self.buffer.len() < size {
+            if !self.fill(false).await? {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.buffer.drain(..size).collect()))
+    }
+
+    /// Read the payload of a binary frame (see ::dataviewer::ipc::Frame),
+    /// or None at the end of the stream.
+    pub async fn read_frame(&mut self) -> Result<Option<Vec<u8>>, glib::Error> {
+        let size = match self.read_exact(4).await? {
+            Some(size) => u32::from_le_bytes(size.try_into().unwrap()) as usize,
+            None => {
+                return Ok(None);
+            }
+        };
+        if size > FRAME_MAX_SIZE {
+            return Err(glib::Error::new(
+                gio::IOErrorEnum::InvalidData,
+                &format!("Frame of {} bytes is too large", size),
+            ));
+        }
+        let frame = self.read_exact(size).await?;
+        if frame.is_some() {
+            self.delimited();
+        }
+        Ok(frame)
+    }
+
+    /// Write data to the output stream, if any
+    pub async fn write_all(&mut self, data: Vec<u8>) -> Result<(), glib::Error> {
+        let output = match &self.output {
+            Some(output) => output,
+            None => {
+                return Ok(());
+            }
+        };
+        let (_, _, error) = output
+            .write_all_future(data, glib::source::Priority::DEFAULT)
+            .await
+            .map_err(|(_, e)| e)?;
+        match error {
+            Some(e) => Err(e),
+            None => Ok(()),
         }
     }
 }
@@ -0,0 +1,141 @@
+//! Ticks of the chart axis
+
+/// Ticks of an axis, placed at "nice" values:
+/// multiples of 1, 2 or 5 times a power of 10.
+pub struct Ticks {
+    step: f64,
+    minor_step: f64,
+    decimals: usize,
+}
+
+impl Ticks {
+    /// Return the ticks with the smallest nice step greater or equal to step
+    pub fn new(step: f64) -> Self {
+        let magnitude = 10f64.powf(step.log10().floor());
+        let mantissa = match step / magnitude {
+            m if m <= 1.0 => 1.0,
+            m if m <= 2.0 => 2.0,
+            m if m <= 5.0 => 5.0,
+            _ => 10.0,
+        };
+        let step = mantissa * magnitude;
+        let minor_step = match mantissa == 2.0 {
+            true => step / 4.0,
+            false => step / 5.0,
+        };
+        Self {
+            step,
+            minor_step,
+            decimals: (-step.log10().floor()).max(0.0) as usize,
+        }
+    }
+
+    /// Return the ticks with the next nice step
+    pub fn next(&self) -> Self {
+        Self::new(self.step * 1.5)
+    }
+
+    /// Return the multiples of step between min and max
+    fn values(step: f64, min: f64, max: f64) -> impl Iterator<Item = f64> {
+        let first = (min / step).ceil() as i64;
+        let last = (max / step).floor() as i64;
+        (first..=last).map(move |i| (i as f64) * step)
+    }
+
+    pub fn major(&self, min: f64, max: f64) -> impl Iterator<Item = f64> {
+        Self::values(self.step, min, max)
+    }
+
+    pub fn minor(&self, min: f64, max: f64) -> impl Iterator<Item = f64> {
+        Self::values(self.minor_step, min, max)
+    }
+
+    pub fn format(&self, value: f64) -> String {
+        format!("{:.*}", self.decimals, value)
+    }
+
+    /// Return the ticks of an axis of size pixels showing min to max,
+    /// or None if the range can not be divided.
+    /// The ticks are widened until their labels, measured by label_size(),
+    /// fit between two ticks.
+    pub fn fit<F>(min: f64, max: f64, size: f64, spacing: f64, label_size: F) -> Option<Self>
+    where
+        F: Fn(&str) -> f64,
+    {
+        let range = max - min;
+        if !range.is_finite() || range <= 0.0 || size <= 0.0 {
+            return None;
+        }
+        let mut ticks = Ticks::new(range * spacing / size);
+        loop {
+            let widest = ticks
+                .major(min, max)
+                .map(|value| label_size(&ticks.format(value)))
+                .fold(0.0, f64::max);
+            let pixels = ticks.step * size / range;
+            if pixels >= widest + 10.0 || pixels >= size {
+                return Some(ticks);
+            }
+            ticks = ticks.next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nice_steps() {
+        let steps = [
+            (0.3, 0.5, 0.1, 1),
+            (1.0, 1.0, 0.2, 0),
+            (1.2, 2.0, 0.5, 0),
+            (3.0, 5.0, 1.0, 0),
+            (7.0, 10.0, 2.0, 0),
+            (0.01, 0.01, 0.002, 2),
+            (250.0, 500.0, 100.0, 0),
+        ];
+        for (step, nice, minor, decimals) in steps {
+            let ticks = Ticks::new(step);
+            assert_eq!(ticks.step, nice, "step {}", step);
+            assert_eq!(ticks.minor_step, minor, "step {}", step);
+            assert_eq!(ticks.decimals, decimals, "step {}", step);
+        }
+    }
+
+    #[test]
+    fn next_steps() {
+        let mut ticks = Ticks::new(0.1);
+        let mut steps = vec![];
+        for _ in 0..6 {
+            ticks = ticks.next();
+            steps.push(ticks.step);
+        }
+        assert_eq!(steps, vec![0.2, 0.5, 1.0, 2.0, 5.0, 10.0]);
+    }
+
+    #[test]
+    fn values() {
+        let ticks = Ticks::new(0.5);
+        let major: Vec<_> = ticks.major(-0.7, 1.2).collect();
+        assert_eq!(major, vec![-0.5, 0.0, 0.5, 1.0]);
+        let minor: Vec<_> = ticks.minor(0.0, 0.5).collect();
+        assert_eq!(minor, vec![0.0, 0.1, 0.2, 0.30000000000000004, 0.4, 0.5]);
+        assert_eq!(ticks.format(0.5), "0.5");
+        assert_eq!(ticks.format(-1.0), "-1.0");
+    }
+
+    #[test]
+    fn fit() {
+        // Labels of 60 pixels do not fit between ticks 10 pixels apart
+        let ticks = Ticks::fit(0.0, 10.0, 100.0, 10.0, |_| 60.0).unwrap();
+        assert_eq!(ticks.step, 10.0);
+        let ticks = Ticks::fit(0.0, 10.0, 100.0, 10.0, |_| 0.0).unwrap();
+        assert_eq!(ticks.step, 1.0);
+
+        assert!(Ticks::fit(1.0, 1.0, 100.0, 10.0, |_| 0.0).is_none());
+        assert!(Ticks::fit(0.0, f64::INFINITY, 100.0, 10.0, |_| 0.0).is_none());
+        assert!(Ticks::fit(0.0, 1.0, 0.0, 10.0, |_| 0.0).is_none());
+    }
+}
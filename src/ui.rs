@@ -1,8 +1,8 @@
 use crate::*;
-use eyre::Result;
-use gtk::glib;
+use eyre::{eyre, Result};
+use gtk::{gio, glib};
 use gtk4 as gtk;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 static ME: &str = "dv";
 
@@ -10,19 +10,29 @@ static ME: &str = "dv";
 pub trait ApplicationDVExt {
     fn new_window(&self) -> gtk::Window;
     fn find_empty_window(&self) -> Option<gtk::Window>;
+    fn find_stream(&self, stream: &str) -> Option<gtk::DrawingArea>;
 }
 
 /// Extend DataViewer Window with some utils functions
 pub trait WindowDVExt {
     fn new_draw_area(&self, file: dataview::File, label: &str) -> Result<gtk::DrawingArea>;
-    fn new_draw_area_from_file(&self, path: &Path) -> Result<gtk::DrawingArea>;
+    fn new_draw_area_from_file(
+        &self,
+        path: &Path,
+        csv_options: &csv::Options,
+    ) -> Result<gtk::DrawingArea>;
+    fn set_auto_reload(&self, draw_area: &gtk::DrawingArea, auto_reload: bool) -> Result<()>;
     fn get_notebook(&self) -> gtk::Notebook;
+    fn current_draw_area(&self) -> Option<gtk::DrawingArea>;
     fn new_open_button(&self) -> gtk::Button;
+    fn new_reload_button(&self) -> gtk::Button;
+    fn new_auto_reload_button(&self) -> gtk::ToggleButton;
     fn new_autoview_button(&self) -> gtk::Button;
     fn new_save_button(&self) -> gtk::Button;
     fn new_export_button(&self) -> gtk::Button;
     fn error_str(&self, msg: &str);
     fn error(&self, e: eyre::Error);
+    fn error_bar(&self, msg: &str);
     fn set_context(&self, context: WindowContext);
     fn get_context(&self) -> &WindowContext;
 }
@@ -33,12 +43,25 @@ pub trait DrawingAreaDVExt {
     fn set_context(&self, context: DrawingAreaContext);
     fn get_context(&self) -> &DrawingAreaContext;
     fn get_mut_context(&mut self) -> &mut DrawingAreaContext;
+    fn reload(&mut self) -> Result<()>;
 }
 
-pub struct WindowContext {}
+pub struct WindowContext {
+    notebook: gtk::Notebook,
+    error_bar: gtk::InfoBar,
+    error_label: gtk::Label,
+}
 
 pub struct DrawingAreaContext {
     pub dataviewer: dataviewer::DataViewer,
+    /// Name of the ipc stream feeding this drawing area
+    pub stream: Option<String>,
+    /// Path of the file shown in this drawing area
+    pub path: Option<PathBuf>,
+    /// Options used to import the file, if it is a CSV file
+    pub csv_options: csv::Options,
+    /// Monitor of the file, when it is automatically reloaded
+    pub monitor: Option<gio::FileMonitor>,
 }
 
 impl ApplicationDVExt for gtk::Application {
@@ -54,16 +77,42 @@ impl ApplicationDVExt for gtk::Application {
             .build()
             .upcast::<gtk::Window>();
 
-        window.set_context(WindowContext {});
-
         // Create the title bar
         let titlebar = gtk::HeaderBar::new();
         // Create the notebook (tabs manager)
         let notebook = gtk::Notebook::new();
-        window.set_child(Some(&notebook));
+        notebook.set_vexpand(true);
+
+        // Create the error bar, reporting errors without blocking the user
+        let error_label = gtk::Label::new(None);
+        error_label.set_wrap(true);
+        error_label.set_selectable(true);
+        let error_bar = gtk::InfoBar::new();
+        error_bar.set_message_type(gtk::MessageType::Error);
+        error_bar.set_show_close_button(true);
+        error_bar.add_child(&error_label);
+        error_bar.set_revealed(false);
+        error_bar.connect_response(|error_bar, _| {
+            error_bar.set_revealed(false);
+        });
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        vbox.append(&error_bar);
+        vbox.append(&notebook);
+        window.set_child(Some(&vbox));
+
+        window.set_context(WindowContext {
+            notebook,
+            error_bar,
+            error_label,
+        });
 
         titlebar.pack_start(&window.new_open_button());
 
+        titlebar.pack_start(&window.new_reload_button());
+
+        titlebar.pack_start(&window.new_auto_reload_button());
+
         titlebar.pack_end(&window.new_save_button());
 
         titlebar.pack_end(&window.new_export_button());
@@ -85,6 +134,26 @@ impl ApplicationDVExt for gtk::Application {
         }
         None
     }
+
+    // Find the drawing area fed by the named ipc stream
+    fn find_stream(&self, stream: &str) -> Option<gtk::DrawingArea> {
+        for window in self.windows() {
+            let pages = window.get_notebook().pages();
+            for i in 0..pages.n_items() {
+                let page = match pages.item(i) {
+                    Some(page) => page.downcast::<gtk::NotebookPage>().unwrap(),
+                    None => {
+                        continue;
+                    }
+                };
+                let draw_area = page.child().downcast::<gtk::DrawingArea>().unwrap();
+                if draw_area.get_context().stream.as_deref() == Some(stream) {
+                    return Some(draw_area);
+                }
+            }
+        }
+        None
+    }
 }
 
 impl WindowDVExt for gtk::Window {
@@ -101,17 +170,72 @@ impl WindowDVExt for gtk::Window {
     }
 
     /// Create a new drawing area from a FILE in a new tab from this Window
-    fn new_draw_area_from_file(&self, path: &Path) -> Result<gtk::DrawingArea> {
+    fn new_draw_area_from_file(
+        &self,
+        path: &Path,
+        csv_options: &csv::Options,
+    ) -> Result<gtk::DrawingArea> {
         let filename = path.file_name().unwrap().to_string_lossy();
-        let string = std::fs::read_to_string(path)?;
-        let file = toml::from_str(&string)?;
-        self.new_draw_area(file, &filename)
+        let file = csv::read_file(path, csv_options)?;
+        let mut draw_area = self.new_draw_area(file, &filename)?;
+        let context = draw_area.get_mut_context();
+        context.path = Some(path.to_path_buf());
+        context.csv_options = csv_options.clone();
+        Ok(draw_area)
+    }
+
+    /// Reload the drawing area each time its file is modified, or stop it
+    fn set_auto_reload(&self, draw_area: &gtk::DrawingArea, auto_reload: bool) -> Result<()> {
+        let mut draw_area = draw_area.clone();
+        if !auto_reload {
+            if let Some(monitor) = draw_area.get_mut_context().monitor.take() {
+                monitor.cancel();
+            }
+            return Ok(());
+        }
+        let path = match &draw_area.get_context().path {
+            Some(path) => path.clone(),
+            None => {
+                return Err(eyre!("Tab was not opened from a file"));
+            }
+        };
+        let monitor = gio::File::for_path(&path)
+            .monitor_file(gio::FileMonitorFlags::NONE, None::<&gio::Cancellable>)?;
+
+        // The monitor is owned by the drawing area (and so by the window):
+        // only keep weak references to them to not leak them.
+        let window_ref = self.downgrade();
+        let draw_area_ref = draw_area.downgrade();
+        monitor.connect_changed(move |_, _, _, event| {
+            if event != gio::FileMonitorEvent::ChangesDoneHint {
+                return;
+            }
+            let (window, mut draw_area) = match (window_ref.upgrade(), draw_area_ref.upgrade()) {
+                (Some(window), Some(draw_area)) => (window, draw_area),
+                _ => {
+                    return;
+                }
+            };
+            println!("Reloading {:?}", path);
+            if let Err(e) = draw_area.reload() {
+                let e = e.wrap_err(format!("Failed to reload {:?}", path));
+                window.error_bar(&format!("{:?}", e));
+            }
+        });
+        draw_area.get_mut_context().monitor = Some(monitor);
+        Ok(())
     }
 
     /// Get Notebook element from this Window
     fn get_notebook(&self) -> gtk::Notebook {
-        let widget = self.child().unwrap();
-        widget.downcast::<gtk::Notebook>().unwrap()
+        self.get_context().notebook.clone()
+    }
+
+    /// Get the drawing area of the current tab
+    fn current_draw_area(&self) -> Option<gtk::DrawingArea> {
+        let notebook = self.get_notebook();
+        let page = notebook.nth_page(notebook.current_page())?;
+        page.downcast::<gtk::DrawingArea>().ok()
     }
 
     fn new_open_button(&self) -> gtk::Button {
@@ -143,7 +267,7 @@ impl WindowDVExt for gtk::Window {
                 }
             };
             println!("Opening {:?}", filename);
-            if let Err(e) = window.new_draw_area_from_file(&filename) {
+            if let Err(e) = window.new_draw_area_from_file(&filename, &csv::Options::default()) {
                 window.error(e.wrap_err(format!("Failed to open {:?}", filename)));
             }
         });
@@ -155,6 +279,61 @@ impl WindowDVExt for gtk::Window {
         button
     }
 
+    fn new_reload_button(&self) -> gtk::Button {
+        let window = self.clone();
+        let button = gtk::Button::with_label("Reload");
+        button.connect_clicked(move |_| {
+            let mut draw_area = match window.current_draw_area() {
+                Some(draw_area) => draw_area,
+                None => {
+                    return;
+                }
+            };
+            if let Err(e) = draw_area.reload() {
+                window.error(e.wrap_err("Failed to reload"));
+            }
+        });
+        button
+    }
+
+    fn new_auto_reload_button(&self) -> gtk::ToggleButton {
+        let window = self.clone();
+        let button = gtk::ToggleButton::with_label("Auto Reload");
+        button.set_sensitive(false);
+        button.connect_toggled(move |button| {
+            let draw_area = match window.current_draw_area() {
+                Some(draw_area) => draw_area,
+                None => {
+                    return;
+                }
+            };
+            // The button was only updated to show the state of the tab
+            let auto_reload = button.is_active();
+            if draw_area.get_context().monitor.is_some() == auto_reload {
+                return;
+            }
+            if let Err(e) = window.set_auto_reload(&draw_area, auto_reload) {
+                window.error(e.wrap_err("Failed to watch the file"));
+                button.set_active(false);
+            }
+        });
+
+        // Show the state of the current tab, once it is switched
+        let window = self.clone();
+        let button_ref = button.clone();
+        self.get_notebook().connect_switch_page(move |_, _, _| {
+            let window = window.clone();
+            let button = button_ref.clone();
+            glib::idle_add_local_once(move || {
+                let draw_area = window.current_draw_area();
+                let context = draw_area.as_ref().map(|draw_area| draw_area.get_context());
+                button.set_sensitive(context.is_some_and(|context| context.path.is_some()));
+                button.set_active(context.is_some_and(|context| context.monitor.is_some()));
+            });
+        });
+        button
+    }
+
     fn new_autoview_button(&self) -> gtk::Button {
         let window = self.clone();
         let button = gtk::Button::with_label("AutoView");
@@ -190,6 +369,14 @@ impl WindowDVExt for gtk::Window {
         );
         dialog.set_current_name("dataviewer.dv.toml");
 
+        // Allow to select the data format, the extension being ambiguous for CSV
+        for format in dataviewer::SaveFormat::ALL {
+            let filter = gtk::FileFilter::new();
+            filter.set_name(Some(format.name()));
+            filter.add_pattern(&format!("*.{}", format.extension()));
+            dialog.add_filter(&filter);
+        }
+
         let window = self.clone();
         dialog.connect_response(move |file, response| {
             file.hide();
@@ -202,29 +389,37 @@ impl WindowDVExt for gtk::Window {
                     return;
                 }
             };
-            let filename = match filename.path() {
+            let mut filename = match filename.path() {
                 Some(filename) => filename,
                 None => {
                     return;
                 }
             };
-            let notebook = window.get_notebook();
-            let i = match notebook.current_page() {
-                Some(i) => i,
-                None => {
-                    return;
+            let name = file.filter().and_then(|filter| filter.name());
+            let selected = dataviewer::SaveFormat::ALL
+                .into_iter()
+                .find(|format| name.as_deref() == Some(format.name()));
+            let format = match (dataviewer::SaveFormat::from_path(&filename), selected) {
+                // The selected filter tells which CSV layout to use
+                (Some(format), Some(selected)) if format.extension() == selected.extension() => {
+                    selected
+                }
+                (Some(format), _) => format,
+                (None, selected) => {
+                    let format = selected.unwrap_or(dataviewer::SaveFormat::Toml);
+                    filename.set_extension(format.extension());
+                    format
                 }
             };
-            let page = match notebook.pages().item(i) {
-                Some(page) => page.downcast::<gtk::NotebookPage>().unwrap(),
+            let mut draw_area = match window.current_draw_area() {
+                Some(draw_area) => draw_area,
                 None => {
                     return;
                 }
             };
-            let mut draw_area = page.child().downcast::<gtk::DrawingArea>().unwrap();
             let context = draw_area.get_mut_context();
             println!("Saving file under {:?}", filename);
-            if let Err(e) = context.dataviewer.save_as(&filename) {
+            if let Err(e) = context.dataviewer.save_as(&filename, format) {
                 window.error(e.wrap_err("Failed to save image"));
             }
         });
@@ -237,16 +432,24 @@ impl WindowDVExt for gtk::Window {
     }
 
     fn new_export_button(&self) -> gtk::Button {
-        // Create the Open File button and Dialog
-        let buttons = [("Export as PNG", gtk::ResponseType::Ok)];
+        // Create the Export button and Dialog
+        let buttons = [("Export", gtk::ResponseType::Ok)];
         let dialog = gtk::FileChooserDialog::new(
-            Some("Export as PNG"),
+            Some("Export"),
             Some(self),
             gtk::FileChooserAction::Save,
             &buttons,
         );
         dialog.set_current_name("dataviewer.png");
 
+        // Allow to select the export format when it is not given by the extension
+        for format in dataviewer::ExportFormat::ALL {
+            let filter = gtk::FileFilter::new();
+            filter.set_name(Some(format.name()));
+            filter.add_pattern(&format!("*.{}", format.extension()));
+            dialog.add_filter(&filter);
+        }
+
         let window = self.clone();
         dialog.connect_response(move |file, response| {
             file.hide();
@@ -259,35 +462,39 @@ impl WindowDVExt for gtk::Window {
                     return;
                 }
             };
-            let filename = match filename.path() {
+            let mut filename = match filename.path() {
                 Some(filename) => filename,
                 None => {
                     return;
                 }
             };
-            let notebook = window.get_notebook();
-            let i = match notebook.current_page() {
-                Some(i) => i,
+            let format = match dataviewer::ExportFormat::from_path(&filename) {
+                Some(format) => format,
                 None => {
-                    return;
+                    let name = file.filter().and_then(|filter| filter.name());
+                    let format = dataviewer::ExportFormat::ALL
+                        .into_iter()
+                        .find(|format| name.as_deref() == Some(format.name()))
+                        .unwrap_or(dataviewer::ExportFormat::Png);
+                    filename.set_extension(format.extension());
+                    format
                 }
             };
-            let page = match notebook.pages().item(i) {
-                Some(page) => page.downcast::<gtk::NotebookPage>().unwrap(),
+            let mut draw_area = match window.current_draw_area() {
+                Some(draw_area) => draw_area,
                 None => {
                     return;
                 }
             };
-            let mut draw_area = page.child().downcast::<gtk::DrawingArea>().unwrap();
-            let draw_area_ref = draw_area.clone();
+            let (width, height) = (draw_area.width(), draw_area.height());
             let context = draw_area.get_mut_context();
             println!("Export image under {:?}", filename);
-            if let Err(e) = context.dataviewer.export_as_png(&draw_area_ref, &filename) {
+            if let Err(e) = context.dataviewer.export(&filename, format, width, height) {
                 window.error(e.wrap_err("Failed to export image"));
             }
         });
 
-        let button = gtk::Button::with_label("Export PNG");
+        let button = gtk::Button::with_label("Export");
         button.connect_clicked(move |_| {
             dialog.present();
         });
@@ -318,6 +525,13 @@ impl WindowDVExt for gtk::Window {
         self.error_str(&msg);
     }
 
+    /// Report an error in the error bar of the window (non-modal)
+    fn error_bar(&self, msg: &str) {
+        let context = self.get_context();
+        context.error_label.set_text(msg);
+        context.error_bar.set_revealed(true);
+    }
+
     fn set_context(&self, context: WindowContext) {
         unsafe {
             self.set_data::<WindowContext>(ME, context);
@@ -331,21 +545,28 @@ impl WindowDVExt for gtk::Window {
 
 // Get or Set our internal context from a Notebook
 impl DrawingAreaDVExt for gtk::DrawingArea {
-    fn from_dataviewer(dataviewer: dataviewer::DataViewer) -> Self {
+    fn from_dataviewer(mut dataviewer: dataviewer::DataViewer) -> Self {
         // Create the Draw Area
         let draw_area = gtk::DrawingArea::new();
         draw_area.set_content_width(128);
         draw_area.set_content_height(128);
+        dataviewer.set_draw_area(&draw_area);
 
         // Set the Draw Area Context
-        draw_area.set_context(DrawingAreaContext { dataviewer });
+        draw_area.set_context(DrawingAreaContext {
+            dataviewer,
+            stream: None,
+            path: None,
+            csv_options: csv::Options::default(),
+            monitor: None,
+        });
 
         // Notify DataViewer when canvas need to be redraw
         draw_area.set_draw_func(move |draw_area, cairo, width, height| {
             let mut draw_area_mut = draw_area.clone();
             let context = draw_area_mut.get_mut_context();
             println!("Draw area {}x{}", width, height);
-            context.dataviewer.draw(draw_area, cairo, width, height);
+            context.dataviewer.draw(cairo, width, height);
         });
 
         // Notify DataViewer when mouse is clicked or released
@@ -418,4 +639,17 @@ impl DrawingAreaDVExt for gtk::DrawingArea {
     fn get_mut_context(&mut self) -> &mut DrawingAreaContext {
         unsafe { self.data::<DrawingAreaContext>(ME).unwrap().as_mut() }
     }
+
+    /// Reload the drawing area from its file
+    fn reload(&mut self) -> Result<()> {
+        let context = self.get_mut_context();
+        let path = match &context.path {
+            Some(path) => path,
+            None => {
+                return Err(eyre!("Tab was not opened from a file"));
+            }
+        };
+        let file = csv::read_file(path, &context.csv_options)?;
+        context.dataviewer.reload(file)
+    }
 }
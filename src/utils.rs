@@ -27,3 +27,30 @@ impl<'a> Iterator for PairIterator<'a> {
         Some((*a, *b))
     }
 }
+
+/// Iterate over a list of y values, computing x from the index:
+/// x = start + index * step
+pub struct LineIterator<'a> {
+    iter: std::iter::Enumerate<std::slice::Iter<'a, f64>>,
+    start: f64,
+    step: f64,
+}
+
+impl<'a> LineIterator<'a> {
+    pub fn new(vec: &'a [f64], start: f64, step: f64) -> Self {
+        Self {
+            iter: vec.iter().enumerate(),
+            start,
+            step,
+        }
+    }
+}
+
+impl<'a> Iterator for LineIterator<'a> {
+    type Item = (f64, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (i, y) = self.iter.next()?;
+        Some((self.start + (i as f64) * self.step, *y))
+    }
+}